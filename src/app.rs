@@ -1,23 +1,60 @@
-use crate::config::AmoebaConfig;
-use crate::query::QueryEngine;
-use crate::response::QueryResponse;
-use crate::theme::{CornerRadius, Margin};
+use crate::batch::BatchAction;
+use crate::config::{AmoebaConfig, ConfigWatcher, ThemeMode};
+use crate::history::{History, HistorySearch};
+use crate::keymap::{Command, Keymap};
+use crate::picker::EnginePicker;
+use crate::query::images;
+use crate::query::parser::{self, Filter, Node, Query};
+use crate::query::waker::Waker;
+use crate::query::{Engine, QueryEngine};
+use crate::response::{QueryResponse, ResultList};
+use crate::theme::{Margin, Theme};
+use crate::window;
 use eframe::epaint::text::{FontInsert, InsertFontFamily};
-use eframe::{App, CreationContext, Frame};
+use eframe::{App, CreationContext, Frame, NativeOptions, Renderer, run_native};
 use egui::epaint::text::FontPriority;
-use egui::{Context, FontFamily, ViewportCommand, Visuals};
+use egui::{Context, FontFamily, Rect, ViewportBuilder, ViewportCommand, Visuals};
 use egui::{TextEdit, TextStyle};
+use egui::{WindowLevel, X11WindowType};
 use flume::Receiver;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PAGE_SIZE: usize = 8;
+// How long arriving results may wait to be shown, so that a burst of them is shown at once.
+const REPAINT_BUDGET: Duration = Duration::from_millis(16);
 
 #[derive(Debug)]
 pub struct AmoebaApp {
     width: f32,
+    bar_height: f32,
+    monitor: Option<Rect>,
+    placement: Option<Rect>,
     query_engine: QueryEngine,
+    waker: Arc<Waker>,
     receiver: Option<Receiver<QueryResponse>>,
-    responses: Vec<QueryResponse>,
-    filter: Option<String>,
+    responses: ResultList,
+    // Result rows are laid out only when in view, so scrolling works from an estimate of
+    // their height and remembers which rows were shown.
+    row_height: f32,
+    shown_rows: Range<usize>,
+    scroll_to_row: Option<usize>,
+    filters: Vec<Filter>,
     active: Option<uuid::Uuid>,
+    selected: Vec<uuid::Uuid>,
+    picker: EnginePicker,
+    history: History,
+    history_index: Option<usize>,
+    history_draft: String,
+    history_search: Option<HistorySearch>,
+    pending_cursor: Option<usize>,
     config: AmoebaConfig,
+    keymap: Keymap,
+    theme: Theme,
+    theme_variant: Option<egui::Theme>,
+    config_watcher: Option<ConfigWatcher>,
+    config_error: Option<String>,
     query_bar: String,
 }
 
@@ -26,8 +63,7 @@ pub enum AmoebaAppCreationError {}
 
 impl AmoebaApp {
     pub fn new(cc: &CreationContext, config: AmoebaConfig) -> Result<Self, AmoebaAppCreationError> {
-        cc.egui_ctx
-            .all_styles_mut(|style| config.theme.update(style));
+        Self::apply_styles(&cc.egui_ctx, &config);
         cc.egui_ctx.add_font(FontInsert::new(
             "FiraCode Nerd Font Mono",
             egui::FontData::from_static(include_bytes!(
@@ -39,20 +75,364 @@ impl AmoebaApp {
             }],
         ));
 
+        images::install(&cc.egui_ctx, &config.query_config.http);
+
+        let waker = Arc::new(Waker::repaint(cc.egui_ctx.clone(), REPAINT_BUDGET));
         Ok(AmoebaApp {
             width: 0.,
-            query_engine: QueryEngine::new(&config.query_config),
-            filter: None,
+            bar_height: 0.,
+            monitor: None,
+            placement: None,
+            query_engine: QueryEngine::new(&config.query_config).with_waker(waker.clone()),
+            waker,
+            filters: Vec::new(),
             query_bar: String::new(),
             receiver: None,
             active: None,
-            responses: Vec::with_capacity(1024),
+            selected: Vec::new(),
+            picker: EnginePicker::default(),
+            history: History::load(
+                AmoebaConfig::history_path()
+                    .inspect_err(|e| log::error!("Failed to locate history: {e}"))
+                    .ok(),
+            ),
+            history_index: None,
+            history_draft: String::new(),
+            history_search: None,
+            pending_cursor: None,
+            responses: ResultList::new(config.query_config.result_limit),
+            row_height: 0.,
+            shown_rows: 0..0,
+            scroll_to_row: None,
+            theme: config.theme.clone(),
+            theme_variant: None,
+            keymap: config.keymap.build().unwrap_or_else(|e| {
+                log::error!("Invalid keymap, using defaults: {e}");
+                Keymap::default()
+            }),
             config,
+            config_watcher: ConfigWatcher::spawn({
+                let ctx = cc.egui_ctx.clone();
+                move || ctx.request_repaint()
+            })
+            .inspect_err(|e| log::error!("Failed to watch config: {e}"))
+            .ok(),
+            config_error: None,
         })
     }
 
+    fn reload_config(&mut self, ctx: &Context) {
+        let Some(watcher) = &self.config_watcher else {
+            return;
+        };
+
+        for config in watcher.drain().collect::<Vec<_>>() {
+            match config {
+                Ok(config) => self.apply_config(ctx, config),
+                Err(err) => {
+                    log::error!("Failed to reload config: {err:#}");
+                    self.config_error = Some(format!("{err:#}"));
+                }
+            }
+        }
+    }
+
+    fn apply_styles(ctx: &Context, config: &AmoebaConfig) {
+        match config.theme_mode() {
+            ThemeMode::Fixed => ctx.all_styles_mut(|style| config.theme.update(style)),
+            mode => {
+                ctx.style_mut_of(egui::Theme::Light, |style| config.light_theme.update(style));
+                ctx.style_mut_of(egui::Theme::Dark, |style| config.dark_theme.update(style));
+                ctx.set_theme(match mode {
+                    ThemeMode::Light => egui::ThemePreference::Light,
+                    ThemeMode::Dark => egui::ThemePreference::Dark,
+                    _ => egui::ThemePreference::System,
+                });
+            }
+        }
+    }
+
+    fn update_theme(&mut self, ctx: &Context) {
+        if self.config.theme_mode() == ThemeMode::Schedule {
+            let now = jiff::Zoned::now().time();
+            let preference = egui::Theme::from(self.config.theme_schedule.theme_at(now)).into();
+            if ctx.options(|o| o.theme_preference) != preference {
+                ctx.set_theme(preference);
+            }
+            ctx.request_repaint_after(self.config.theme_schedule.until_next_switch(now));
+        }
+
+        let variant = ctx.theme();
+        if self.theme_variant != Some(variant) {
+            log::info!("Switching to {variant:?} theme");
+            self.theme = self.config.theme_for(variant.into()).clone();
+            self.theme_variant = Some(variant);
+        }
+    }
+
+    fn apply_config(&mut self, ctx: &Context, mut config: AmoebaConfig) {
+        config.theme_mode_override = self.config.theme_mode_override;
+        config.incognito_override = self.config.incognito_override;
+        Self::apply_styles(ctx, &config);
+        self.theme_variant = None;
+
+        if config.query_config != self.config.query_config {
+            log::info!("Rebuilding query engine: {:?}", config.query_config);
+            self.query_engine = self.query_engine.rebuild(&config.query_config);
+            images::install(ctx, &config.query_config.http);
+            self.responses.set_limit(config.query_config.result_limit);
+            self.receiver = None;
+
+            if !self.query_bar.trim().is_empty() {
+                self.query_engine.query(&self.full_query());
+            }
+        }
+
+        match config.keymap.build() {
+            Ok(keymap) => self.keymap = keymap,
+            Err(e) => log::error!("Invalid keymap, keeping previous keymap: {e}"),
+        }
+
+        if config.window != self.config.window {
+            self.monitor = None;
+        }
+
+        self.config = config;
+        self.config_error = None;
+    }
+
+    fn visible_len(&self) -> usize {
+        self.config
+            .window
+            .max_results
+            .map_or(self.responses.len(), |max| self.responses.len().min(max))
+    }
+
+    fn place_window(&mut self, ctx: &Context) {
+        if self.monitor.is_none() {
+            let ppp = ctx
+                .input(|i| i.viewport().native_pixels_per_point)
+                .unwrap_or(1.0);
+            self.monitor = window::find(self.config.window.monitor)
+                .map(|rect| Rect::from_min_max((rect.min / ppp).round(), (rect.max / ppp).round()))
+                .or_else(|| window::fallback(ctx.input(|i| i.viewport().monitor_size)));
+            self.placement = None;
+            log::info!("Placing window on monitor {:?}", self.monitor);
+        }
+
+        let height = ctx.used_size().y;
+        let Some(monitor) = self.monitor else {
+            self.width = ctx.viewport_rect().width();
+            // Viewport commands repaint, so only send one when the size actually changes.
+            let placement = Rect::from_min_size(egui::Pos2::ZERO, (self.width, height).into());
+            if self.placement != Some(placement) {
+                ctx.send_viewport_cmd(ViewportCommand::InnerSize(placement.size()));
+                self.placement = Some(placement);
+            }
+            return;
+        };
+
+        let placement = self
+            .config
+            .window
+            .placement(monitor, height, self.bar_height);
+        self.width = placement.width();
+
+        if self.placement != Some(placement) {
+            if self.placement.map(|p| p.min) != Some(placement.min) {
+                ctx.send_viewport_cmd(ViewportCommand::OuterPosition(placement.min));
+            }
+            ctx.send_viewport_cmd(ViewportCommand::InnerSize(placement.size()));
+            self.placement = Some(placement);
+        }
+    }
+
+    // Rows outside the ones last shown aren't laid out, so they can't scroll themselves
+    // into view.
+    fn scroll_into_view(&mut self, ctx: &Context, row: usize) {
+        if row <= self.shown_rows.start || row + 1 >= self.shown_rows.end {
+            self.scroll_to_row = Some(row);
+            ctx.request_repaint();
+        }
+    }
+
+    fn toggle_selected(&mut self, uuid: uuid::Uuid) {
+        if let Some(pos) = self.selected.iter().position(|u| *u == uuid) {
+            self.selected.remove(pos);
+        } else {
+            self.selected.push(uuid);
+        }
+    }
+
+    fn select_range(&mut self, from: usize, to: usize) {
+        for resp in &self.responses[from.min(to)..=from.max(to)] {
+            if !self.selected.contains(&resp.get_uuid()) {
+                self.selected.push(resp.get_uuid());
+            }
+        }
+    }
+
+    // Selected results in display order, falling back to the active result.
+    fn batch_items(&self) -> Vec<String> {
+        self.responses
+            .iter()
+            .filter(|resp| {
+                if self.selected.is_empty() {
+                    self.active == Some(resp.get_uuid())
+                } else {
+                    self.selected.contains(&resp.get_uuid())
+                }
+            })
+            .filter_map(|resp| resp.item.copy_text())
+            .collect()
+    }
+
+    fn run_batch(&self, ctx: &Context, action: BatchAction) {
+        let items = self.batch_items();
+        if !items.is_empty() {
+            self.config.batch.run(&action, &items, ctx);
+        }
+    }
+
+    fn commit_filter(&mut self, prefix: &str) {
+        self.filters.push(Filter {
+            prefix: prefix.to_string(),
+            flags: Vec::new(),
+            args: None,
+            exclude: false,
+            span: 0..0,
+        });
+        self.query_bar.clear();
+        self.picker = EnginePicker::default();
+        log::info!(
+            "Filters: {:?}, Query Bar: {:?}",
+            self.filters,
+            self.query_bar
+        );
+        self.clear_query();
+    }
+
+    // Moves the filters at the start of the query bar that have been completed with a
+    // space, and that the cursor has moved past, into `filters`. Returns the number of
+    // chars removed from the query bar.
+    fn take_leading_filters(&mut self, cursor: usize) -> usize {
+        let query = Query::parse(&self.query_bar);
+        let mut cut = 0;
+        for node in query.nodes {
+            let Node::Filter(filter) = node else { break };
+            let end = filter.span.end;
+            let completed = self
+                .query_bar
+                .chars()
+                .nth(end)
+                .is_some_and(char::is_whitespace);
+            if !completed || cursor <= end {
+                break;
+            }
+            self.filters.push(filter);
+            cut = end + 1;
+        }
+
+        self.query_bar
+            .drain(..parser::byte_offset(&self.query_bar, cut));
+        cut
+    }
+
+    fn full_query(&self) -> String {
+        self.filters
+            .iter()
+            .map(Filter::to_string)
+            .chain(std::iter::once(self.query_bar.clone()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Replaces the filters and query bar with `query`, moving the cursor to its end.
+    fn set_full_query(&mut self, query: &str) {
+        self.filters.clear();
+        self.query_bar = format!("{query} ");
+        self.take_leading_filters(usize::MAX);
+        self.query_bar.truncate(self.query_bar.trim_end().len());
+        self.pending_cursor = Some(self.query_bar.chars().count());
+        self.refresh_query();
+    }
+
+    fn record_history(&mut self, executed: uuid::Uuid) {
+        if !self.config.incognito() {
+            let query = self.full_query();
+            self.history.push(&query, &self.config.history);
+            if let Some(resp) = self.responses.iter().find(|r| r.get_uuid() == executed) {
+                resp.record(&query);
+            }
+        }
+        self.history_index = None;
+    }
+
+    fn recall_history(&mut self, older: bool) {
+        let index = match (self.history_index, older) {
+            (None, true) if !self.history.is_empty() => {
+                self.history_draft = self.full_query();
+                0
+            }
+            (Some(i), true) => (i + 1).min(self.history.len() - 1),
+            (Some(0), false) => {
+                self.history_index = None;
+                let draft = std::mem::take(&mut self.history_draft);
+                self.set_full_query(&draft);
+                return;
+            }
+            (Some(i), false) => i - 1,
+            (None, _) => return,
+        };
+
+        self.history_index = Some(index);
+        if let Some(entry) = self.history.recent(index).map(str::to_string) {
+            self.set_full_query(&entry);
+        }
+    }
+
+    fn start_history_search(&mut self) {
+        self.history_search = Some(HistorySearch::new(self.full_query()));
+        self.filters.clear();
+        self.query_bar.clear();
+        self.clear_query();
+    }
+
+    fn end_history_search(&mut self, accept: bool) {
+        let Some(search) = self.history_search.take() else {
+            return;
+        };
+
+        let query = if accept {
+            self.history
+                .search(&self.query_bar)
+                .get(search.active)
+                .map_or(search.saved, |entry| entry.to_string())
+        } else {
+            search.saved
+        };
+        self.set_full_query(&query);
+    }
+
+    fn picker_engines(&self) -> Vec<Engine> {
+        if self.history_search.is_some() {
+            return Vec::new();
+        }
+        EnginePicker::matches(&self.query_bar, self.query_engine.engines())
+    }
+
+    fn refresh_query(&mut self) {
+        if self.history_search.is_some() || !self.picker_engines().is_empty() {
+            self.clear_query();
+        } else if !self.query_bar.trim().is_empty() {
+            self.request_query();
+        } else {
+            self.clear_query();
+        }
+    }
+
     fn request_query(&mut self) {
-        self.query_engine.query(&self.query_bar, &self.filter);
+        self.query_engine.query(&self.full_query());
     }
 
     fn clear_query(&mut self) {
@@ -62,197 +442,587 @@ impl AmoebaApp {
 
 impl App for AmoebaApp {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
-        if let Some(rcv) = &self.receiver
-            && self.query_engine.match_receiver(rcv)
+        self.reload_config(ctx);
+        self.update_theme(ctx);
+
+        let mut commands = self.keymap.pressed(ctx, &[Command::PopFilter]);
+
+        if let Some(search) = &mut self.history_search {
+            let matches = self.history.search(&self.query_bar).len();
+            let mut end = None;
+            for command in &commands {
+                match command {
+                    Command::Prev | Command::HistorySearch => {
+                        search.active = (search.active + 1).min(matches.saturating_sub(1))
+                    }
+                    Command::Next => search.active = search.active.saturating_sub(1),
+                    Command::Execute | Command::Complete => end = Some(true),
+                    Command::Close | Command::Clear => end = Some(false),
+                    _ => {}
+                }
+            }
+            commands.retain(|command| {
+                !matches!(
+                    command,
+                    Command::Next
+                        | Command::Prev
+                        | Command::HistorySearch
+                        | Command::Execute
+                        | Command::Complete
+                        | Command::Close
+                        | Command::Clear
+                )
+            });
+            if let Some(accept) = end {
+                self.end_history_search(accept);
+            }
+        }
+
+        let engines = self.picker_engines();
+        if !engines.is_empty() {
+            for command in &commands {
+                match command {
+                    Command::Next => self.picker.next(engines.len()),
+                    Command::Prev => self.picker.prev(engines.len()),
+                    Command::Execute | Command::Complete => {
+                        let engine = &engines[self.picker.active.min(engines.len() - 1)];
+                        self.commit_filter(engine.prefix());
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            commands.retain(|command| {
+                !matches!(
+                    command,
+                    Command::Next | Command::Prev | Command::Execute | Command::Complete
+                )
+            });
+        }
+
+        if commands.contains(&Command::Clear) {
+            self.query_bar.clear();
+            self.filters.clear();
+            self.clear_query();
+        }
+
+        let results_empty = self.visible_len() == 0;
+        for command in &commands {
+            match command {
+                Command::HistoryPrev => self.recall_history(true),
+                Command::HistoryNext => self.recall_history(false),
+                Command::Prev if results_empty => self.recall_history(true),
+                Command::Next if results_empty && self.history_index.is_some() => {
+                    self.recall_history(false)
+                }
+                Command::HistorySearch => self.start_history_search(),
+                _ => {}
+            }
+        }
+
+        if !self
+            .receiver
+            .as_ref()
+            .is_some_and(|rcv| self.query_engine.match_receiver(rcv))
         {
-            self.responses.extend(rcv.drain());
-            self.responses.sort_by_key(|i| -i.priority);
-        } else {
             self.active = None;
+            self.selected.clear();
             self.receiver = self.query_engine.responses();
             self.responses.clear();
         }
+        // Also read right after switching to a new query's receiver, since a fast query may
+        // have sent everything, and spent its wake, before this frame.
+        if let Some(rcv) = &self.receiver {
+            receive(&self.waker, rcv, &mut self.responses);
+        }
 
         let have_responses = !self.responses.is_empty();
 
-        let mut active_idx = None;
+        // Found up front, since the active row may be scrolled out of view and not laid out.
+        let active_idx = self
+            .active
+            .and_then(|active| self.responses.iter().position(|r| r.get_uuid() == active));
+        let mut selection_click = None;
+        let mut batch_action = None;
+        // The result that ran, to record the query in the history for.
+        let mut executed = None;
+
+        // Run against the results rather than the rows laid out, which the active row or the
+        // row for a quick-execute index may not be among.
+        if let Some(resp) = active_idx.map(|i| &self.responses[i]) {
+            for &command in &commands {
+                if command == Command::Copy && !self.selected.is_empty() {
+                    continue;
+                }
+                if resp.run(command, ctx)
+                    && matches!(command, Command::Execute | Command::ExecuteAlt)
+                {
+                    executed = Some(resp.get_uuid());
+                }
+            }
+        }
+        for &command in &commands {
+            if let Command::ExecuteIndex(n) = command
+                && let Some(i) = quick_execute_row(&self.shown_rows, n)
+                && i < self.visible_len()
+            {
+                let resp = &self.responses[i];
+                self.active = Some(resp.get_uuid());
+                if resp.run(Command::Execute, ctx) {
+                    executed = Some(resp.get_uuid());
+                }
+            }
+        }
 
         let query_panel_frame = egui::Frame::NONE
             .fill(ctx.style().visuals.window_fill())
             .stroke(egui::Stroke::NONE)
             .shadow(egui::Shadow::NONE)
-            .inner_margin(self.config.theme.margin)
+            .inner_margin(self.theme.margin)
             .outer_margin(Margin::symmetric(0, -2))
             .corner_radius(if have_responses {
-                self.config.theme.query_corner_radius_with_results
+                self.theme.query_corner_radius_with_results
             } else {
-                self.config.theme.query_corner_radius
+                self.theme.query_corner_radius
             });
 
         egui::TopBottomPanel::top("Amoeba")
             .frame(egui::Frame::NONE)
             .show(ctx, |ui| {
-                query_panel_frame.show(ui, |ui| {
-                    ui.vertical(|ui| {
-                        ui.add_space(2.0);
-                        ui.horizontal(|ui| {
-                            ui.vertical(|ui| {
-                                ui.add_space(2.0);
-
-                                ui.horizontal(|ui| {
-                                    self.query_engine.icon(&self.filter, ui);
+                if let Some(err) = &self.config_error {
+                    egui::Frame::NONE
+                        .fill(self.theme.window_fill.to())
+                        .inner_margin(self.theme.margin)
+                        .corner_radius(self.theme.query_corner_radius.to())
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "Config error, keeping previous config: {err}"
+                                ))
+                                .color(self.theme.error_fg_color.to()),
+                            );
+                        });
+                    ui.add_space(4.0);
+                }
+
+                self.bar_height = query_panel_frame
+                    .show(ui, |ui| {
+                        ui.vertical(|ui| {
+                            ui.add_space(2.0);
+                            ui.horizontal(|ui| {
+                                ui.vertical(|ui| {
+                                    ui.add_space(2.0);
+
+                                    ui.horizontal(|ui| {
+                                        if self.history_search.is_some() {
+                                            ui.monospace("󰋚");
+                                        } else if self.filters.is_empty() {
+                                            self.query_engine.icon(None, ui);
+                                        }
+                                        for filter in &self.filters {
+                                            self.query_engine.icon(Some(filter), ui);
+                                        }
+                                    });
                                 });
-                            });
 
-                            let response = ui.add_sized(
-                                [
-                                    ui.available_width(),
-                                    ui.text_style_height(&TextStyle::Body) + 4.0,
-                                ],
-                                TextEdit::singleline(&mut self.query_bar)
-                                    .font(TextStyle::Monospace)
-                                    .return_key(None)
-                                    .desired_width(f32::INFINITY)
-                                    .lock_focus(true)
-                                    .frame(false)
-                                    .id_source("query_edit"),
-                            );
+                                let response = ui.add_sized(
+                                    [
+                                        ui.available_width(),
+                                        ui.text_style_height(&TextStyle::Body) + 4.0,
+                                    ],
+                                    TextEdit::singleline(&mut self.query_bar)
+                                        .font(TextStyle::Monospace)
+                                        .return_key(None)
+                                        .desired_width(f32::INFINITY)
+                                        .lock_focus(true)
+                                        .frame(false)
+                                        .id_source("query_edit"),
+                                );
 
-                            response.request_focus();
-
-                            if response.has_focus()
-                                && let Some(mut state) = TextEdit::load_state(ctx, response.id)
-                                && let Some(range) = state.cursor.char_range()
-                                && let Some(pos) = range.single()
-                            {
-                                if pos.index == 0
-                                    && ctx.input(|i| {
-                                        i.key_pressed(egui::Key::Backspace)
-                                            || i.key_pressed(egui::Key::ArrowLeft)
-                                    })
+                                response.request_focus();
+
+                                if response.has_focus()
+                                    && let Some(mut state) = TextEdit::load_state(ctx, response.id)
+                                    && let Some(range) = state.cursor.char_range()
+                                    && let Some(pos) = range.single()
                                 {
-                                    let filter = self.filter.take();
-                                    if let Some(filter) = filter {
+                                    if let Some(cursor) = self.pending_cursor.take() {
                                         state.cursor.set_char_range(Some(
                                             egui::text::CCursorRange::one(
-                                                egui::text::CCursor::new(filter.len()),
+                                                egui::text::CCursor::new(cursor),
                                             ),
                                         ));
-                                        self.query_bar = filter + " " + &self.query_bar;
                                         state.store(ctx, response.id);
-                                        self.request_query();
+                                        ctx.request_repaint();
+                                    } else if pos.index == 0
+                                        && commands.contains(&Command::PopFilter)
+                                    {
+                                        if let Some(filter) = self.filters.pop() {
+                                            let filter = filter.to_string();
+                                            state.cursor.set_char_range(Some(
+                                                egui::text::CCursorRange::one(
+                                                    egui::text::CCursor::new(
+                                                        filter.chars().count(),
+                                                    ),
+                                                ),
+                                            ));
+                                            self.query_bar = filter + " " + &self.query_bar;
+                                            state.store(ctx, response.id);
+                                            self.request_query();
+                                        }
+                                        log::info!(
+                                            "Filters: {:?}, Query Bar: {:?}",
+                                            self.filters,
+                                            self.query_bar
+                                        );
+                                    } else if self.history_search.is_none() {
+                                        let cut = self.take_leading_filters(pos.index);
+                                        if cut > 0 {
+                                            state.cursor.set_char_range(Some(
+                                                egui::text::CCursorRange::one(pos - cut),
+                                            ));
+                                            state.store(ctx, response.id);
+
+                                            log::info!(
+                                                "Filters: {:?}, Query Bar: {:?}",
+                                                self.filters,
+                                                self.query_bar
+                                            );
+
+                                            self.request_query();
+                                        }
                                     }
-                                    log::info!(
-                                        "Filter: {:?}, Query Bar: {:?}",
-                                        self.filter,
-                                        self.query_bar
-                                    );
-                                } else if self.query_bar.starts_with('@')
-                                    && let Some(idx) = self.query_bar.find(' ')
-                                    && pos.index > idx
-                                {
-                                    state.cursor.set_char_range(Some(
-                                        egui::text::CCursorRange::one(pos - idx - 1),
-                                    ));
-                                    let mut tmp = self.query_bar.split_off(idx + 1);
-                                    std::mem::swap(&mut self.query_bar, &mut tmp);
-                                    tmp.pop();
-                                    self.filter.replace(tmp);
-                                    state.store(ctx, response.id);
-
-                                    log::info!(
-                                        "Filter: {:?}, Query Bar: {:?}",
-                                        self.filter,
-                                        self.query_bar
-                                    );
-
-                                    self.request_query();
                                 }
-                            }
 
-                            if response.changed() || response.lost_focus() {
-                                if !self.query_bar.trim().is_empty() {
-                                    self.request_query();
-                                } else {
-                                    self.clear_query();
+                                if response.changed() {
+                                    self.picker = EnginePicker::default();
+                                    self.history_index = None;
+                                    if let Some(search) = &mut self.history_search {
+                                        search.active = 0;
+                                    }
                                 }
+
+                                if response.changed() || response.lost_focus() {
+                                    self.refresh_query();
+                                }
+                            });
+                        });
+                    })
+                    .response
+                    .rect
+                    .height();
+
+                if let Some(search) = &mut self.history_search {
+                    let matches = self.history.search(&self.query_bar);
+                    let mut clicked = None;
+                    egui::ScrollArea::vertical()
+                        .min_scrolled_height(self.theme.max_height)
+                        .show(ui, |ui| {
+                            clicked =
+                                search.ui(ui, &self.theme, self.width, &self.query_bar, &matches);
+                        });
+                    if let Some(i) = clicked {
+                        search.active = i;
+                        self.end_history_search(true);
+                    }
+                    return;
+                }
+
+                let engines = self.picker_engines();
+                if !engines.is_empty() {
+                    egui::ScrollArea::vertical()
+                        .min_scrolled_height(self.theme.max_height)
+                        .show(ui, |ui| {
+                            if let Some(i) = self.picker.ui(ui, &self.theme, self.width, &engines) {
+                                self.commit_filter(engines[i].prefix());
                             }
                         });
-                    });
-                });
+                    return;
+                }
 
-                egui::ScrollArea::vertical()
-                    .min_scrolled_height(self.config.theme.max_height)
-                    .show(ui, |ui| {
-                        for (i, resp) in self.responses.iter().enumerate() {
-                            let is_active = if let Some(active) = self.active
-                                && active == resp.get_uuid()
-                            {
-                                active_idx = Some(i);
-                                true
-                            } else {
-                                false
-                            };
-
-                            let panel_frame = egui::Frame::NONE
-                                .fill(
-                                    if is_active {
-                                        self.config.theme.active_bg_fill
-                                    } else {
-                                        self.config.theme.window_fill
-                                    }
-                                    .to(),
-                                )
-                                .stroke(egui::Stroke::NONE)
-                                .shadow(egui::Shadow::NONE)
-                                .inner_margin(self.config.theme.margin)
-                                .outer_margin(Margin::symmetric(0, -2))
-                                .corner_radius(
-                                    if i < self.responses.len() - 1 {
-                                        CornerRadius::NONE
-                                    } else {
-                                        self.config.theme.query_corner_radius_with_results.flip_y()
+                let row_height = if self.row_height > 0. {
+                    self.row_height
+                } else {
+                    2. * ui.text_style_height(&TextStyle::Body)
+                };
+                let row_stride = row_height + ui.spacing().item_spacing.y;
+                let mut scroll_area =
+                    egui::ScrollArea::vertical().min_scrolled_height(self.theme.max_height);
+                if let Some(row) = self.scroll_to_row.take() {
+                    let offset = if row <= self.shown_rows.start {
+                        row as f32 * row_stride
+                    } else {
+                        (row + 1) as f32 * row_stride - self.theme.max_height
+                    };
+                    scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.));
+                }
+
+                scroll_area.show_rows(ui, row_height, self.visible_len(), |ui, rows| {
+                    let visible_len = self.visible_len();
+                    let show_badges = self.keymap.quick_execute_held(ctx);
+                    let pointer_moving = ui.input(|i| i.pointer.is_moving());
+                    let modifiers = ui.input(|i| i.modifiers);
+                    let mut new_active = None;
+                    self.shown_rows = rows.clone();
+
+                    for i in rows.clone() {
+                        let resp = &self.responses[i];
+                        let is_active = active_idx == Some(i);
+                        let is_hovered = ui
+                            .ctx()
+                            .read_response(resp.row_id(ui))
+                            .is_some_and(|r| r.hovered());
+
+                        let fill = if is_hovered {
+                            self.theme.hovered_bg_fill
+                        } else if is_active {
+                            self.theme.active_bg_fill
+                        } else if self.selected.contains(&resp.get_uuid()) {
+                            self.theme.selected_bg_fill
+                        } else {
+                            self.theme.window_fill
+                        };
+                        let panel_frame = self.theme.row_frame(fill, i == visible_len - 1);
+
+                        let badge = show_badges.then(|| quick_execute_badge(&rows, i)).flatten();
+                        let response =
+                            resp.ui(ui, panel_frame, &self.theme, self.width, is_active, badge);
+                        // The shortest row, so the rows shown always fill the view.
+                        if self.row_height <= 0. || response.rect.height() < self.row_height {
+                            self.row_height = response.rect.height();
+                        }
+
+                        response.context_menu(|ui| {
+                            if !self.selected.is_empty() {
+                                let count = self.selected.len();
+                                if ui.button(format!("Copy {count} paths")).clicked() {
+                                    batch_action = Some(BatchAction::Copy);
+                                }
+                                if ui.button(format!("Open {count} in editor")).clicked() {
+                                    batch_action = Some(BatchAction::OpenInEditor);
+                                }
+                                for (i, script) in self.config.batch.scripts.iter().enumerate() {
+                                    if ui.button(&script.name).clicked() {
+                                        batch_action = Some(BatchAction::Script(i));
                                     }
-                                    .to(),
-                                );
+                                }
+                                if batch_action.is_some() {
+                                    ui.close();
+                                }
+                                ui.separator();
+                            }
+                            resp.secondary_menu(ui);
+                        });
 
-                            resp.ui(ui, panel_frame, &self.config.theme, self.width, is_active);
+                        if (response.hovered() && pointer_moving)
+                            || response.clicked()
+                            || response.secondary_clicked()
+                        {
+                            new_active = Some(resp.get_uuid());
                         }
-                    });
+
+                        if response.clicked() && modifiers.command {
+                            selection_click = Some((i, false));
+                        } else if response.clicked() && modifiers.shift {
+                            selection_click = Some((i, true));
+                        } else if (response.double_clicked()
+                            || (self.config.single_click_execute && response.clicked()))
+                            && resp.run(Command::Execute, ui.ctx())
+                        {
+                            executed = Some(resp.get_uuid());
+                        }
+                    }
+
+                    if let Some(uuid) = new_active {
+                        self.active = Some(uuid);
+                    }
+                });
             });
 
-        if ctx.input(|i| i.keys_down.contains(&egui::Key::Escape)) {
+        match selection_click {
+            Some((i, false)) => self.toggle_selected(self.responses[i].get_uuid()),
+            Some((i, true)) => self.select_range(active_idx.unwrap_or(i), i),
+            None => {}
+        }
+
+        if let Some(uuid) = executed {
+            self.record_history(uuid);
+        }
+
+        if let Some(action) = batch_action {
+            self.run_batch(ctx, action);
+        }
+
+        if commands.contains(&Command::Copy) && !self.selected.is_empty() {
+            self.run_batch(ctx, BatchAction::Copy);
+        }
+
+        if commands.contains(&Command::OpenSelected) {
+            self.run_batch(ctx, BatchAction::OpenInEditor);
+        }
+
+        if commands.contains(&Command::Close) {
             ctx.send_viewport_cmd(ViewportCommand::Close);
         }
 
-        if have_responses {
-            if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-                if let Some(active) = active_idx
-                    && active < self.responses.len() - 1
-                {
-                    self.active.replace(self.responses[active + 1].get_uuid());
-                } else {
-                    self.active.replace(self.responses[0].get_uuid());
-                }
-            } else if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-                if let Some(active) = active_idx
-                    && active > 0
-                {
-                    self.active.replace(self.responses[active - 1].get_uuid());
-                } else {
-                    self.active
-                        .replace(self.responses[self.responses.len() - 1].get_uuid());
+        let visible_len = self.visible_len();
+        if visible_len > 0 {
+            let last = visible_len - 1;
+            let target = commands.iter().rev().find_map(|command| {
+                Some(match (command, active_idx) {
+                    (Command::Next, Some(i)) if i < last => i + 1,
+                    (Command::Next, _) => 0,
+                    (Command::Prev, Some(i)) if i > 0 => i - 1,
+                    (Command::Prev, _) => last,
+                    (Command::PageDown, Some(i)) => (i + PAGE_SIZE).min(last),
+                    (Command::PageDown, None) => PAGE_SIZE.min(last),
+                    (Command::PageUp, Some(i)) => i.saturating_sub(PAGE_SIZE),
+                    (Command::PageUp, None) => 0,
+                    (Command::First, _) => 0,
+                    (Command::Last, _) => last,
+                    _ => return None,
+                })
+            });
+
+            if let Some(target) = target {
+                self.active.replace(self.responses[target].get_uuid());
+                self.scroll_into_view(ctx, target);
+            }
+
+            for command in &commands {
+                match (command, active_idx) {
+                    (Command::ToggleSelect, Some(i)) => {
+                        self.toggle_selected(self.responses[i].get_uuid())
+                    }
+                    (Command::SelectNext, Some(i)) if i < last => {
+                        self.select_range(i, i + 1);
+                        self.active.replace(self.responses[i + 1].get_uuid());
+                    }
+                    (Command::SelectPrev, Some(i)) if i > 0 => {
+                        self.select_range(i - 1, i);
+                        self.active.replace(self.responses[i - 1].get_uuid());
+                    }
+                    (Command::SelectNext | Command::SelectPrev, None) => {
+                        self.active.replace(self.responses[0].get_uuid());
+                    }
+                    _ => {}
                 }
             }
         }
 
-        self.width = ctx.viewport_rect().width();
-        ctx.send_viewport_cmd(ViewportCommand::InnerSize(
-            (self.width, ctx.used_size().y).into(),
-        ));
+        self.place_window(ctx);
     }
 
     fn clear_color(&self, _visuals: &Visuals) -> [f32; 4] {
         [0.; 4]
     }
 }
+
+// Takes what has arrived on `rcv`, so that responses arriving afterwards wake again.
+fn receive(waker: &Waker, rcv: &Receiver<QueryResponse>, responses: &mut ResultList) {
+    waker.reset();
+    responses.extend(rcv.drain());
+}
+
+// Quick-execute badges number the rows in view, so they stay usable when scrolled.
+const QUICK_EXECUTE_ROWS: usize = 9;
+
+fn quick_execute_badge(shown_rows: &Range<usize>, row: usize) -> Option<usize> {
+    let n = row.checked_sub(shown_rows.start)?;
+    (n < QUICK_EXECUTE_ROWS).then_some(n + 1)
+}
+
+fn quick_execute_row(shown_rows: &Range<usize>, n: u8) -> Option<usize> {
+    let row = shown_rows.start + n as usize;
+    (usize::from(n) < QUICK_EXECUTE_ROWS && shown_rows.contains(&row)).then_some(row)
+}
+
+/// Opens the launcher window and blocks until it's closed.
+pub fn run(config: AmoebaConfig) -> anyhow::Result<()> {
+    let err = run_native(
+        "Amoeba",
+        NativeOptions {
+            renderer: Renderer::Wgpu,
+            viewport: ViewportBuilder {
+                active: Some(true),
+                decorations: Some(false),
+                mouse_passthrough: Some(false),
+                titlebar_shown: Some(true),
+                transparent: Some(true),
+                window_level: Some(WindowLevel::AlwaysOnTop),
+                window_type: Some(X11WindowType::Dock),
+                app_id: Some("amoeba".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Box::new(|cc| Ok(Box::new(AmoebaApp::new(cc, config)?))),
+    );
+
+    if let Err(ref err) = err {
+        anyhow::bail!("Initialization Error: {err}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{Icon, ResultItem};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn receives_what_a_new_query_sent_before_the_switch() {
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker = Waker::new({
+            let wakes = wakes.clone();
+            move || {
+                wakes.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let response =
+            |title: &str| QueryResponse::new(ResultItem::new(Icon::Glyph("".into()), title), 0);
+        let mut responses = ResultList::new(10);
+
+        let (_, old) = flume::unbounded::<QueryResponse>();
+        receive(&waker, &old, &mut responses);
+
+        // The new query finishes, waking once, before the frame that switches to it.
+        let (snd, new) = flume::unbounded();
+        snd.send(response("fast")).unwrap();
+        waker.wake();
+        assert_eq!(wakes.load(Ordering::Relaxed), 1);
+
+        receive(&waker, &new, &mut responses);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].item.title, "fast");
+
+        // Later responses wake again.
+        snd.send(response("slow")).unwrap();
+        waker.wake();
+        assert_eq!(wakes.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn quick_execute_is_relative_to_the_rows_in_view() {
+        let shown = 20..32;
+        assert_eq!(quick_execute_badge(&shown, 20), Some(1));
+        assert_eq!(quick_execute_badge(&shown, 28), Some(9));
+        assert_eq!(quick_execute_badge(&shown, 29), None);
+        assert_eq!(quick_execute_badge(&shown, 3), None);
+
+        assert_eq!(quick_execute_row(&shown, 0), Some(20));
+        assert_eq!(quick_execute_row(&shown, 8), Some(28));
+        assert_eq!(quick_execute_row(&shown, 9), None);
+        // Badges only go up to the last row in view.
+        assert_eq!(quick_execute_row(&(0..3), 3), None);
+
+        for row in shown.clone() {
+            if let Some(badge) = quick_execute_badge(&shown, row) {
+                assert_eq!(quick_execute_row(&shown, badge as u8 - 1), Some(row));
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct BatchConfig {
+    pub editor: Option<String>,
+    pub scripts: Vec<BatchScript>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchScript {
+    pub name: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchAction {
+    Copy,
+    OpenInEditor,
+    Script(usize),
+}
+
+impl BatchConfig {
+    pub fn editor(&self) -> String {
+        self.editor
+            .clone()
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .unwrap_or_else(|| "xdg-open".to_string())
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn run(&self, action: &BatchAction, items: &[String], ctx: &egui::Context) {
+        match action {
+            BatchAction::Copy => ctx.copy_text(items.join("\n")),
+            BatchAction::OpenInEditor => {
+                let editor = self.editor();
+                // `xdg-open` only accepts a single argument.
+                if editor == "xdg-open" {
+                    for item in items {
+                        spawn(&editor, std::slice::from_ref(item));
+                    }
+                } else {
+                    spawn(&editor, items);
+                }
+            }
+            BatchAction::Script(i) => {
+                if let Some(script) = self.scripts.get(*i) {
+                    spawn(&script.command, items);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+fn spawn(command: &str, args: &[String]) {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        log::error!("Empty batch command");
+        return;
+    };
+
+    log::info!("Running batch command `{command}` on {} items", args.len());
+    match std::process::Command::new(program)
+        .args(parts)
+        .args(args)
+        .spawn()
+    {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => log::error!("Failed to run `{command}`: {e}"),
+    }
+}
@@ -1,10 +1,432 @@
+use crate::batch::BatchConfig;
+use crate::history::HistoryConfig;
+#[cfg(feature = "gui")]
+use crate::keymap::KeymapConfig;
 use crate::query::QueryConfig;
-use crate::theme::Theme;
+use crate::theme::{Theme, ThemeLoader};
+use crate::window::WindowConfig;
+use flume::Receiver;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+pub const APP_NAME: &str = "amoeba";
+pub const CONFIG_NAME: &str = "config";
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+const THEME_KEYS: [&str; 3] = ["theme", "light_theme", "dark_theme"];
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(default)]
 pub struct AmoebaConfig {
     pub theme: Theme,
+    pub light_theme: Theme,
+    pub dark_theme: Theme,
+    pub theme_mode: ThemeMode,
+    pub theme_schedule: ThemeSchedule,
+    #[serde(skip)]
+    pub theme_mode_override: Option<ThemeMode>,
+    // The theme files and color schemes the themes were resolved from, to watch for changes.
+    #[serde(skip)]
+    pub theme_sources: Vec<PathBuf>,
+    pub window: WindowConfig,
+    pub single_click_execute: bool,
+    #[cfg(feature = "gui")]
+    pub keymap: KeymapConfig,
+    pub batch: BatchConfig,
+    pub history: HistoryConfig,
+    #[serde(skip)]
+    pub incognito_override: bool,
     pub query_config: QueryConfig,
 }
+
+impl Default for AmoebaConfig {
+    fn default() -> Self {
+        AmoebaConfig {
+            theme: Theme::default(),
+            light_theme: Theme::light(),
+            dark_theme: Theme::default(),
+            theme_mode: ThemeMode::default(),
+            theme_schedule: ThemeSchedule::default(),
+            theme_mode_override: None,
+            theme_sources: Vec::new(),
+            window: WindowConfig::default(),
+            single_click_execute: false,
+            #[cfg(feature = "gui")]
+            keymap: KeymapConfig::default(),
+            batch: BatchConfig::default(),
+            history: HistoryConfig::default(),
+            incognito_override: false,
+            query_config: QueryConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    #[default]
+    Fixed,
+    System,
+    Schedule,
+    Light,
+    Dark,
+}
+
+impl std::str::FromStr for ThemeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(ThemeMode::Fixed),
+            "system" => Ok(ThemeMode::System),
+            "schedule" => Ok(ThemeMode::Schedule),
+            "light" => Ok(ThemeMode::Light),
+            "dark" => Ok(ThemeMode::Dark),
+            _ => anyhow::bail!("Unknown theme mode: {s}"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ThemeVariant {
+    Light,
+    Dark,
+}
+
+#[cfg(feature = "gui")]
+impl From<ThemeVariant> for egui::Theme {
+    fn from(variant: ThemeVariant) -> Self {
+        match variant {
+            ThemeVariant::Light => egui::Theme::Light,
+            ThemeVariant::Dark => egui::Theme::Dark,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl From<egui::Theme> for ThemeVariant {
+    fn from(theme: egui::Theme) -> Self {
+        match theme {
+            egui::Theme::Light => ThemeVariant::Light,
+            egui::Theme::Dark => ThemeVariant::Dark,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Copy, Clone)]
+pub struct ThemeSchedule {
+    pub light: jiff::civil::Time,
+    pub dark: jiff::civil::Time,
+}
+
+impl Default for ThemeSchedule {
+    fn default() -> Self {
+        ThemeSchedule {
+            light: jiff::civil::time(7, 0, 0, 0),
+            dark: jiff::civil::time(19, 0, 0, 0),
+        }
+    }
+}
+
+impl ThemeSchedule {
+    pub fn theme_at(&self, now: jiff::civil::Time) -> ThemeVariant {
+        let is_light = if self.light <= self.dark {
+            self.light <= now && now < self.dark
+        } else {
+            now >= self.light || now < self.dark
+        };
+
+        if is_light {
+            ThemeVariant::Light
+        } else {
+            ThemeVariant::Dark
+        }
+    }
+
+    pub fn until_next_switch(&self, now: jiff::civil::Time) -> Duration {
+        [self.light, self.dark]
+            .into_iter()
+            .filter_map(|switch| {
+                let span = now.until(switch).ok()?;
+                let secs = span.total(jiff::Unit::Second).ok()?;
+                Some(if secs > 0. { secs } else { secs + 86400. })
+            })
+            .map(Duration::from_secs_f64)
+            .min()
+            .unwrap_or(Duration::from_secs(60))
+    }
+}
+
+impl AmoebaConfig {
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            let config = Self::default();
+            confy::store_path(&path, &config)?;
+            return Ok(config);
+        }
+
+        Self::load_path(&path)
+    }
+
+    pub fn theme_mode(&self) -> ThemeMode {
+        self.theme_mode_override.unwrap_or(self.theme_mode)
+    }
+
+    pub fn incognito(&self) -> bool {
+        self.incognito_override || self.history.incognito
+    }
+
+    pub fn theme_for(&self, variant: ThemeVariant) -> &Theme {
+        match (self.theme_mode(), variant) {
+            (ThemeMode::Fixed, _) => &self.theme,
+            (_, ThemeVariant::Light) => &self.light_theme,
+            (_, ThemeVariant::Dark) => &self.dark_theme,
+        }
+    }
+
+    pub fn path() -> Result<PathBuf, confy::ConfyError> {
+        confy::get_configuration_file_path(APP_NAME, Some(CONFIG_NAME))
+    }
+
+    pub fn themes_dir() -> Result<PathBuf, confy::ConfyError> {
+        let path = Self::path()?;
+        Ok(path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+            .join("themes"))
+    }
+
+    pub fn history_path() -> Result<PathBuf, confy::ConfyError> {
+        let path = Self::path()?;
+        Ok(path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+            .join("history"))
+    }
+
+    pub fn load_path(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut table: toml::Table = toml::from_str(&contents)?;
+
+        let loader = ThemeLoader::new(path.parent().map(|dir| dir.join("themes")));
+        for key in THEME_KEYS {
+            if let Some(theme) = table.remove(key) {
+                table.insert(
+                    key.to_string(),
+                    toml::Value::try_from(loader.resolve(theme)?)?,
+                );
+            }
+        }
+
+        let mut config: Self = table.try_into()?;
+        #[cfg(feature = "gui")]
+        config.keymap.build()?;
+        config.theme_sources = loader.sources();
+
+        Ok(config)
+    }
+}
+
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    rcv: Receiver<anyhow::Result<AmoebaConfig>>,
+}
+
+impl ConfigWatcher {
+    pub fn spawn(notify: impl Fn() + Send + 'static) -> anyhow::Result<Self> {
+        Self::watch(AmoebaConfig::path()?, notify)
+    }
+
+    // Reloads the config at `path` whenever its modification time, or that of a theme file
+    // it uses, changes, calling `notify` after each reload is sent.
+    fn watch(path: PathBuf, notify: impl Fn() + Send + 'static) -> anyhow::Result<Self> {
+        let (snd, rcv) = flume::unbounded();
+        // Taken before returning, so that no change made afterwards is missed.
+        let mut paths = watched_paths(&path, &AmoebaConfig::load_path(&path));
+        let mut last_modified = modified(&paths);
+
+        std::thread::Builder::new()
+            .name("amoeba-config-watcher".to_string())
+            .spawn(move || {
+                while !snd.is_disconnected() {
+                    std::thread::sleep(WATCH_INTERVAL);
+
+                    let current = modified(&paths);
+                    if current[0].is_none() || current == last_modified {
+                        continue;
+                    }
+
+                    log::info!("Reloading config from {path:?}");
+                    let config = AmoebaConfig::load_path(&path);
+                    if config.is_ok() {
+                        paths = watched_paths(&path, &config);
+                    }
+                    last_modified = modified(&paths);
+                    if snd.send(config).is_err() {
+                        break;
+                    }
+                    notify();
+                }
+            })?;
+
+        Ok(Self { rcv })
+    }
+
+    pub fn drain(&self) -> impl Iterator<Item = anyhow::Result<AmoebaConfig>> + '_ {
+        self.rcv.drain()
+    }
+}
+
+// The config file followed by the theme files it was resolved from.
+fn watched_paths(path: &Path, config: &anyhow::Result<AmoebaConfig>) -> Vec<PathBuf> {
+    let mut paths = vec![path.to_path_buf()];
+    if let Ok(config) = config {
+        paths.extend(config.theme_sources.iter().cloned());
+    }
+    paths
+}
+
+fn modified(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| path.metadata().and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // A temporary config directory, removed when dropped.
+    struct ConfigDir(PathBuf);
+
+    impl ConfigDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("amoeba-config-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(dir.join("themes")).unwrap();
+            ConfigDir(dir)
+        }
+
+        // Writes `contents` to `name` in the directory, returning its path.
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for ConfigDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Rewrites `path` with `contents`, dated ahead so that coarse filesystem timestamps can't
+    // hide the change.
+    fn rewrite(path: &Path, contents: &str) {
+        std::fs::write(path, contents).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+    }
+
+    // Waits for `watcher` to send a reloaded config.
+    fn reloaded(watcher: &ConfigWatcher) -> AmoebaConfig {
+        let start = Instant::now();
+        loop {
+            if let Some(config) = watcher.drain().next() {
+                return config.unwrap();
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "no reload");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn watcher_reloads_rewritten_config() {
+        let dir = ConfigDir::new();
+        let path = dir.write("config.toml", "single_click_execute = false\n");
+
+        let watcher = ConfigWatcher::watch(path.clone(), || {}).unwrap();
+        rewrite(&path, "single_click_execute = true\n");
+        assert!(reloaded(&watcher).single_click_execute);
+    }
+
+    #[test]
+    fn watcher_reloads_edited_theme() {
+        let dir = ConfigDir::new();
+        let path = dir.write("config.toml", "theme = \"mine\"\n");
+        let theme = dir.write("themes/mine.toml", "font_size = 20.0\n");
+
+        let watcher = ConfigWatcher::watch(path.clone(), || {}).unwrap();
+        rewrite(&theme, "font_size = 25.0\n");
+        let config = reloaded(&watcher);
+        assert_eq!(config.theme.font_size, 25.);
+        assert_eq!(config.theme_sources, [theme]);
+    }
+
+    #[test]
+    fn schedule_picks_the_theme_for_the_time() {
+        let at = |h, m| jiff::civil::time(h, m, 0, 0);
+        let day = ThemeSchedule::default();
+        for (now, variant) in [
+            (at(6, 59), ThemeVariant::Dark),
+            (at(7, 0), ThemeVariant::Light),
+            (at(12, 0), ThemeVariant::Light),
+            (at(19, 0), ThemeVariant::Dark),
+            (at(23, 0), ThemeVariant::Dark),
+        ] {
+            assert_eq!(day.theme_at(now), variant, "{now}");
+        }
+
+        // Light across midnight.
+        let night = ThemeSchedule {
+            light: at(20, 0),
+            dark: at(6, 0),
+        };
+        for (now, variant) in [
+            (at(20, 0), ThemeVariant::Light),
+            (at(23, 30), ThemeVariant::Light),
+            (at(3, 0), ThemeVariant::Light),
+            (at(6, 0), ThemeVariant::Dark),
+            (at(12, 0), ThemeVariant::Dark),
+        ] {
+            assert_eq!(night.theme_at(now), variant, "{now}");
+        }
+    }
+
+    #[test]
+    fn schedule_waits_for_the_next_switch() {
+        let at = |h, m| jiff::civil::time(h, m, 0, 0);
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        let day = ThemeSchedule::default();
+        assert_eq!(day.until_next_switch(at(6, 0)), hours(1));
+        assert_eq!(day.until_next_switch(at(12, 0)), hours(7));
+        // At a switch, the next one is the other.
+        assert_eq!(day.until_next_switch(at(7, 0)), hours(12));
+        assert_eq!(day.until_next_switch(at(20, 0)), hours(11));
+
+        let night = ThemeSchedule {
+            light: at(20, 0),
+            dark: at(6, 0),
+        };
+        assert_eq!(night.until_next_switch(at(21, 0)), hours(9));
+        assert_eq!(night.until_next_switch(at(6, 0)), hours(14));
+    }
+}
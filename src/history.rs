@@ -0,0 +1,274 @@
+#[cfg(feature = "gui")]
+use crate::theme::Theme;
+#[cfg(feature = "gui")]
+use egui::{Ui, text::LayoutJob};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub max_entries: usize,
+    pub incognito: bool,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            max_entries: 1000,
+            incognito: false,
+        }
+    }
+}
+
+// Submitted queries, including their filters, oldest first. Stored one per line.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|path| match std::fs::read_to_string(path) {
+                Ok(contents) => Some(contents),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    log::error!("Failed to read history from {path:?}: {e}");
+                    None
+                }
+            })
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        History { entries, path }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The `n`th most recent entry.
+    pub fn recent(&self, n: usize) -> Option<&str> {
+        self.entries.iter().rev().nth(n).map(String::as_str)
+    }
+
+    pub fn push(&mut self, query: &str, config: &HistoryConfig) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|entry| entry != query);
+        self.entries.push(query.to_string());
+        let excess = self.entries.len().saturating_sub(config.max_entries);
+        self.entries.drain(..excess);
+
+        if let Some(path) = &self.path {
+            let mut contents = self.entries.join("\n");
+            contents.push('\n');
+            if let Err(e) = std::fs::write(path, contents) {
+                log::error!("Failed to write history to {path:?}: {e}");
+            }
+        }
+    }
+
+    // Most recent entries containing `pattern`, ignoring case.
+    pub fn search(&self, pattern: &str) -> Vec<&str> {
+        let pattern = pattern.to_lowercase();
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.to_lowercase().contains(&pattern))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+// Reverse-incremental search over the history: the query bar holds the search pattern while
+// the filters and text that were there before are kept aside to restore on cancel.
+#[derive(Debug, Default)]
+pub struct HistorySearch {
+    pub active: usize,
+    pub saved: String,
+}
+
+impl HistorySearch {
+    pub fn new(saved: String) -> Self {
+        HistorySearch { active: 0, saved }
+    }
+
+    // Returns the index of a clicked row.
+    #[cfg(feature = "gui")]
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        theme: &Theme,
+        width: f32,
+        pattern: &str,
+        matches: &[&str],
+    ) -> Option<usize> {
+        if matches.is_empty() {
+            let frame = theme.row_frame(theme.window_fill, true);
+            frame.show(ui, |ui| {
+                ui.set_width(width - (theme.margin.left + theme.margin.right) as f32);
+                ui.weak(format!("No history matching `{pattern}`"));
+            });
+            return None;
+        }
+
+        self.active = self.active.min(matches.len() - 1);
+        let mut clicked = None;
+
+        for (i, entry) in matches.iter().enumerate() {
+            let id = ui.id().with(("history", i));
+            let is_hovered = ui.ctx().read_response(id).is_some_and(|r| r.hovered());
+            let fill = if is_hovered {
+                theme.hovered_bg_fill
+            } else if i == self.active {
+                theme.active_bg_fill
+            } else {
+                theme.window_fill
+            };
+
+            let rect = theme
+                .row_frame(fill, i == matches.len() - 1)
+                .show(ui, |ui| {
+                    ui.set_width(width - (theme.margin.left + theme.margin.right) as f32);
+                    ui.horizontal(|ui| {
+                        ui.monospace("󰋚");
+                        ui.label(highlight(ui, theme, entry, pattern));
+                    });
+                })
+                .response
+                .rect;
+
+            let response = ui.interact(rect, id, egui::Sense::click());
+            if response.hovered() && ui.input(|i| i.pointer.is_moving()) {
+                self.active = i;
+            }
+            if response.clicked() {
+                clicked = Some(i);
+            }
+        }
+
+        clicked
+    }
+}
+
+#[cfg(feature = "gui")]
+fn highlight(ui: &Ui, theme: &Theme, entry: &str, pattern: &str) -> LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let normal =
+        egui::TextFormat::simple(font_id.clone(), theme.noninteractive_fg_stroke.color().to());
+    let matched = egui::TextFormat {
+        background: theme.selection_bg_fill.to(),
+        ..normal.clone()
+    };
+
+    let mut job = LayoutJob::default();
+    let lower = entry.to_lowercase();
+    // Lowercasing can change byte lengths, in which case the match can't be mapped back.
+    let needle = pattern.to_lowercase();
+    let found = (!needle.is_empty() && lower.len() == entry.len())
+        .then(|| lower.find(&needle))
+        .flatten()
+        .filter(|&start| entry.is_char_boundary(start + needle.len()));
+
+    match found {
+        Some(start) => {
+            let end = start + needle.len();
+            job.append(&entry[..start], 0., normal.clone());
+            job.append(&entry[start..end], 0., matched);
+            job.append(&entry[end..], 0., normal);
+        }
+        None => job.append(entry, 0., normal),
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A history file in the temp dir, removed when dropped.
+    struct HistoryFile(PathBuf);
+
+    impl HistoryFile {
+        fn new() -> Self {
+            HistoryFile(
+                std::env::temp_dir().join(format!("amoeba-history-{}", uuid::Uuid::new_v4())),
+            )
+        }
+    }
+
+    impl Drop for HistoryFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn entries(history: &History) -> Vec<&str> {
+        (0..history.len())
+            .rev()
+            .filter_map(|n| history.recent(n))
+            .collect()
+    }
+
+    #[test]
+    fn pushes_recent_entries_and_saves_them() {
+        let file = HistoryFile::new();
+        let config = HistoryConfig {
+            max_entries: 3,
+            ..Default::default()
+        };
+        let mut history = History::load(Some(file.0.clone()));
+        assert!(history.is_empty());
+
+        for query in ["a", "@fzf b", "  ", "c", " a "] {
+            history.push(query, &config);
+        }
+        // Repeats move to the end, and blank queries are skipped.
+        assert_eq!(entries(&history), ["@fzf b", "c", "a"]);
+        assert_eq!(history.recent(0), Some("a"));
+
+        history.push("d", &config);
+        assert_eq!(entries(&history), ["c", "a", "d"]);
+
+        assert_eq!(std::fs::read_to_string(&file.0).unwrap(), "c\na\nd\n");
+        let reloaded = History::load(Some(file.0.clone()));
+        assert_eq!(entries(&reloaded), ["c", "a", "d"]);
+    }
+
+    #[test]
+    fn searches_most_recent_first() {
+        let config = HistoryConfig::default();
+        let mut history = History::load(None);
+        for query in ["Rust book", "@wiki rust", "python", "trusty"] {
+            history.push(query, &config);
+        }
+
+        assert_eq!(
+            history.search("RUST"),
+            ["trusty", "@wiki rust", "Rust book"]
+        );
+        assert_eq!(history.search("@wiki"), ["@wiki rust"]);
+        assert_eq!(
+            history.search(""),
+            ["trusty", "python", "@wiki rust", "Rust book"]
+        );
+        assert!(history.search("java").is_empty());
+    }
+}
@@ -0,0 +1,465 @@
+use egui::{Context, Key, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    Next,
+    Prev,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    Execute,
+    ExecuteAlt,
+    Clear,
+    PopFilter,
+    Close,
+    Copy,
+    ToggleSelect,
+    SelectNext,
+    SelectPrev,
+    OpenSelected,
+    Complete,
+    HistoryPrev,
+    HistoryNext,
+    HistorySearch,
+    #[serde(skip)]
+    ExecuteIndex(u8),
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Chord {
+    pub const fn new(modifiers: Modifiers, key: Key) -> Self {
+        Chord { modifiers, key }
+    }
+
+    pub const fn key(key: Key) -> Self {
+        Chord::new(Modifiers::NONE, key)
+    }
+
+    pub const fn ctrl(key: Key) -> Self {
+        Chord::new(Modifiers::CTRL, key)
+    }
+
+    pub const fn alt(key: Key) -> Self {
+        Chord::new(Modifiers::ALT, key)
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::str::FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = match s.rsplit_once('+') {
+            Some((modifiers, key)) => (modifiers.parse::<ModifierSet>()?.0, key.trim()),
+            None => (Modifiers::NONE, s.trim()),
+        };
+        let key = Key::from_name(key).ok_or_else(|| format!("invalid key in chord `{s}`"))?;
+
+        Ok(Chord { modifiers, key })
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.any() {
+            write!(f, "{}+", ModifierSet(self.modifiers))?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+// A set of modifiers like `Ctrl+Shift`; the empty string is no modifiers.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct ModifierSet(pub Modifiers);
+
+impl std::str::FromStr for ModifierSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::NONE;
+        for part in s.split('+').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" | "meta" | "option" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "cmd" | "super" => modifiers.mac_cmd = true,
+                _ => return Err(format!("invalid modifier `{part}` in `{s}`")),
+            }
+        }
+
+        Ok(ModifierSet(modifiers))
+    }
+}
+
+impl TryFrom<String> for ModifierSet {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::fmt::Display for ModifierSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Modifiers {
+            alt,
+            ctrl,
+            shift,
+            mac_cmd,
+            ..
+        } = self.0;
+        let names: Vec<&str> = [
+            (ctrl, "Ctrl"),
+            (alt, "Alt"),
+            (shift, "Shift"),
+            (mac_cmd, "Cmd"),
+        ]
+        .into_iter()
+        .filter_map(|(held, name)| held.then_some(name))
+        .collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
+
+impl From<ModifierSet> for String {
+    fn from(modifiers: ModifierSet) -> Self {
+        modifiers.to_string()
+    }
+}
+
+impl From<Chord> for String {
+    fn from(chord: Chord) -> Self {
+        chord.to_string()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeymapPreset {
+    #[default]
+    Default,
+    Emacs,
+    Vim,
+}
+
+impl KeymapPreset {
+    pub fn bindings(self) -> BTreeMap<Command, Vec<Chord>> {
+        use Command::*;
+
+        let shift = |key| Chord::new(Modifiers::SHIFT, key);
+        let ctrl_shift = |key| Chord::new(Modifiers::CTRL | Modifiers::SHIFT, key);
+        let alt_shift = |key| Chord::new(Modifiers::ALT | Modifiers::SHIFT, key);
+
+        let mut bindings = BTreeMap::from([
+            (Next, vec![Chord::key(Key::ArrowDown)]),
+            (Prev, vec![Chord::key(Key::ArrowUp)]),
+            (PageDown, vec![Chord::key(Key::PageDown)]),
+            (PageUp, vec![Chord::key(Key::PageUp)]),
+            (First, vec![Chord::ctrl(Key::Home)]),
+            (Last, vec![Chord::ctrl(Key::End)]),
+            (Execute, vec![Chord::key(Key::Enter)]),
+            (ExecuteAlt, vec![shift(Key::Enter)]),
+            (Clear, vec![Chord::ctrl(Key::L)]),
+            (
+                PopFilter,
+                vec![Chord::key(Key::Backspace), Chord::key(Key::ArrowLeft)],
+            ),
+            (Close, vec![Chord::key(Key::Escape)]),
+            (Copy, vec![ctrl_shift(Key::C)]),
+            (ToggleSelect, vec![Chord::ctrl(Key::Space)]),
+            (SelectNext, vec![shift(Key::ArrowDown)]),
+            (SelectPrev, vec![shift(Key::ArrowUp)]),
+            (OpenSelected, vec![Chord::ctrl(Key::O)]),
+            (Complete, vec![Chord::key(Key::Tab)]),
+            (HistoryPrev, vec![Chord::ctrl(Key::P)]),
+            (HistoryNext, vec![Chord::ctrl(Key::N)]),
+            (HistorySearch, vec![Chord::ctrl(Key::R)]),
+        ]);
+
+        if self != KeymapPreset::Default {
+            // Ctrl+P/Ctrl+N move through results here; on an empty result list they still
+            // recall history as `prev`/`next` do.
+            bindings.remove(&HistoryPrev);
+            bindings.remove(&HistoryNext);
+        }
+
+        let mut extend = |command, chords: &[Chord]| {
+            bindings
+                .entry(command)
+                .or_default()
+                .extend_from_slice(chords)
+        };
+
+        match self {
+            KeymapPreset::Default => {}
+            KeymapPreset::Emacs => {
+                extend(Next, &[Chord::ctrl(Key::N)]);
+                extend(Prev, &[Chord::ctrl(Key::P)]);
+                extend(PageDown, &[Chord::ctrl(Key::V)]);
+                extend(PageUp, &[Chord::alt(Key::V)]);
+                extend(First, &[alt_shift(Key::Comma)]);
+                extend(Last, &[alt_shift(Key::Period)]);
+                extend(Execute, &[Chord::ctrl(Key::J)]);
+                extend(Close, &[Chord::ctrl(Key::G)]);
+                extend(Copy, &[Chord::alt(Key::W)]);
+            }
+            KeymapPreset::Vim => {
+                extend(Next, &[Chord::ctrl(Key::J), Chord::ctrl(Key::N)]);
+                extend(Prev, &[Chord::ctrl(Key::K), Chord::ctrl(Key::P)]);
+                extend(PageDown, &[Chord::ctrl(Key::D)]);
+                extend(PageUp, &[Chord::ctrl(Key::U)]);
+                extend(ExecuteAlt, &[Chord::ctrl(Key::Enter)]);
+                extend(Close, &[Chord::ctrl(Key::OpenBracket)]);
+                extend(Copy, &[Chord::ctrl(Key::Y)]);
+            }
+        }
+
+        bindings
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeymapConfig {
+    pub preset: KeymapPreset,
+    pub bindings: BTreeMap<Command, Vec<Chord>>,
+    pub quick_execute: ModifierSet,
+}
+
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        KeymapConfig {
+            preset: KeymapPreset::default(),
+            bindings: BTreeMap::new(),
+            quick_execute: ModifierSet(Modifiers::ALT),
+        }
+    }
+}
+
+const QUICK_EXECUTE_KEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{chord}` is bound to both `{first:?}` and `{second:?}`")]
+pub struct KeymapConflict {
+    pub chord: Chord,
+    pub first: Command,
+    pub second: Command,
+}
+
+impl KeymapConfig {
+    pub fn build(&self) -> Result<Keymap, KeymapConflict> {
+        let mut bindings = self.preset.bindings();
+        bindings.extend(self.bindings.clone());
+
+        let quick_execute = self.quick_execute.0.any().then_some(self.quick_execute.0);
+        if let Some(modifiers) = quick_execute {
+            for (i, key) in QUICK_EXECUTE_KEYS.into_iter().enumerate() {
+                bindings.insert(
+                    Command::ExecuteIndex(i as u8),
+                    vec![Chord::new(modifiers, key)],
+                );
+            }
+        }
+
+        let mut keymap: Vec<(Chord, Command)> = Vec::new();
+        for (command, chords) in bindings {
+            for chord in chords {
+                match keymap.iter().find(|(c, _)| *c == chord) {
+                    Some((_, first)) if *first != command => {
+                        return Err(KeymapConflict {
+                            chord,
+                            first: *first,
+                            second: command,
+                        });
+                    }
+                    Some(_) => {}
+                    None => keymap.push((chord, command)),
+                }
+            }
+        }
+
+        Ok(Keymap {
+            bindings: keymap,
+            quick_execute,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Chord, Command)>,
+    quick_execute: Option<Modifiers>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        KeymapConfig::default()
+            .build()
+            .expect("default keymap has no conflicts")
+    }
+}
+
+impl Keymap {
+    // Consumes the key events of every bound command except those in `passthrough`,
+    // which are only reported so that widgets (e.g. the query `TextEdit`) still see them.
+    pub fn pressed(&self, ctx: &Context, passthrough: &[Command]) -> Vec<Command> {
+        ctx.input_mut(|i| {
+            let mut commands = Vec::new();
+            // Chords with Alt still type text, which follows their key event.
+            let mut consume_text = false;
+            i.events.retain(|event| {
+                if let egui::Event::Text(_) = event
+                    && std::mem::take(&mut consume_text)
+                {
+                    return false;
+                }
+                consume_text = false;
+
+                let egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } = event
+                else {
+                    return true;
+                };
+
+                let Some((_, command)) = self.bindings.iter().find(|(chord, _)| {
+                    chord.key == *key && modifiers.matches_exact(chord.modifiers)
+                }) else {
+                    return true;
+                };
+
+                commands.push(*command);
+                if passthrough.contains(command) {
+                    return true;
+                }
+                consume_text = modifiers.alt || modifiers.ctrl || modifiers.command;
+                false
+            });
+            commands
+        })
+    }
+
+    pub fn quick_execute_held(&self, ctx: &Context) -> bool {
+        self.quick_execute
+            .is_some_and(|modifiers| ctx.input(|i| i.modifiers.matches_exact(modifiers)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_build_without_conflicts() {
+        for preset in [
+            KeymapPreset::Default,
+            KeymapPreset::Emacs,
+            KeymapPreset::Vim,
+        ] {
+            let config = KeymapConfig {
+                preset,
+                ..Default::default()
+            };
+            if let Err(conflict) = config.build() {
+                panic!("{preset:?} preset has a conflict: {conflict:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn consumes_text_typed_by_chords() {
+        let keymap = KeymapConfig {
+            preset: KeymapPreset::Emacs,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let key = |key, modifiers| egui::Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        };
+        let text = |text: &str| egui::Event::Text(text.to_string());
+        let input = egui::RawInput {
+            events: vec![
+                key(Key::V, Modifiers::ALT),
+                text("v"),
+                key(Key::Comma, Modifiers::ALT | Modifiers::SHIFT),
+                text("<"),
+                // Unbound, so typed as usual.
+                key(Key::X, Modifiers::NONE),
+                text("x"),
+            ],
+            ..Default::default()
+        };
+
+        let ctx = Context::default();
+        let mut pressed = Vec::new();
+        let mut left = Vec::new();
+        let _ = ctx.run(input, |ctx| {
+            pressed = keymap.pressed(ctx, &[]);
+            left = ctx.input(|i| i.events.clone());
+        });
+        assert_eq!(pressed, [Command::PageUp, Command::First]);
+        assert_eq!(left, [key(Key::X, Modifiers::NONE), text("x")]);
+    }
+
+    #[test]
+    fn reports_duplicate_chords() {
+        let chord: Chord = "ctrl+q".parse().unwrap();
+        let config = KeymapConfig {
+            bindings: BTreeMap::from([
+                (Command::Close, vec![chord]),
+                (Command::Clear, vec![chord]),
+            ]),
+            ..Default::default()
+        };
+        let conflict = config.build().unwrap_err();
+        assert_eq!(conflict.chord, chord);
+        assert_eq!(
+            [conflict.first, conflict.second],
+            [Command::Clear, Command::Close]
+        );
+
+        // Rebinding a preset's chord to another command conflicts with the command that
+        // keeps it, unless that one is rebound too.
+        let config = KeymapConfig {
+            bindings: BTreeMap::from([(Command::Close, vec![Chord::key(Key::Enter)])]),
+            ..Default::default()
+        };
+        assert!(config.build().is_err());
+    }
+}
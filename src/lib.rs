@@ -0,0 +1,23 @@
+//! Amoeba is a keyboard-driven launcher that fans each query out to a set of search engines
+//! and merges their results as they stream in.
+//!
+//! The core (engines, queries, config and themes) builds without the launcher window and
+//! its egui/eframe stack, so it can be embedded in other tools. To add an engine, implement
+//! [`query::SearchEngine`] and register it with a [`query::QueryEngine`], which then runs
+//! queries against it alongside the built-in ones. With the `gui` feature, [`app::run`]
+//! shows the launcher window.
+
+pub mod batch;
+pub mod config;
+pub mod history;
+pub mod query;
+pub mod response;
+pub mod theme;
+pub mod window;
+
+#[cfg(feature = "gui")]
+pub mod app;
+#[cfg(feature = "gui")]
+pub mod keymap;
+#[cfg(feature = "gui")]
+mod picker;
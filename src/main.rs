@@ -1,13 +1,5 @@
-mod app;
-mod config;
-mod query;
-mod response;
-mod theme;
-
-use crate::app::AmoebaApp;
-use crate::config::AmoebaConfig;
-use eframe::{NativeOptions, Renderer, egui, run_native};
-use egui::{ViewportBuilder, WindowLevel, X11WindowType};
+use amoeba::config::{AmoebaConfig, ThemeMode};
+use amoeba::theme::ThemeImport;
 
 fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
@@ -17,31 +9,67 @@ fn main() -> anyhow::Result<()> {
         .filter_module("amoeba", log::LevelFilter::Trace)
         .init();
 
-    let config: AmoebaConfig = confy::load("amoeba", Some("config"))?;
-
-    let err = run_native(
-        "Amoeba",
-        NativeOptions {
-            centered: true,
-            renderer: Renderer::Wgpu,
-            viewport: ViewportBuilder {
-                active: Some(true),
-                decorations: Some(false),
-                mouse_passthrough: Some(false),
-                titlebar_shown: Some(true),
-                transparent: Some(true),
-                window_level: Some(WindowLevel::AlwaysOnTop),
-                window_type: Some(X11WindowType::Dock),
-                app_id: Some("amoeba".to_string()),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        Box::new(|cc| Ok(Box::new(AmoebaApp::new(cc, config)?))),
-    );
-
-    if let Err(ref err) = err {
-        anyhow::bail!("Initialization Error: {err}");
+    let mut args = std::env::args().skip(1).peekable();
+    if let Some(command) = args.next_if(|arg| !arg.starts_with("--")) {
+        return match command.as_str() {
+            "import-theme" => import_theme(args),
+            _ => anyhow::bail!("Unknown command: {command}"),
+        };
+    }
+
+    let mut config = AmoebaConfig::load()?;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--light" => config.theme_mode_override = Some(ThemeMode::Light),
+            "--dark" => config.theme_mode_override = Some(ThemeMode::Dark),
+            "--incognito" => config.incognito_override = true,
+            "--theme-mode" => {
+                let mode = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--theme-mode requires a value"))?;
+                config.theme_mode_override = Some(mode.parse()?);
+            }
+            _ => anyhow::bail!("Unknown argument: {arg}"),
+        }
+    }
+
+    amoeba::app::run(config)
+}
+
+fn import_theme(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    const USAGE: &str =
+        "Usage: amoeba import-theme <path> [--format base16|pywal|alacritty] [--name <name>]";
+
+    let mut path = None;
+    let mut format = None;
+    let mut name = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = Some(args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?.parse()?)
+            }
+            "--name" => name = Some(args.next().ok_or_else(|| anyhow::anyhow!(USAGE))?),
+            _ if path.is_none() => path = Some(arg.into()),
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+
+    let import = ThemeImport::Spec {
+        path: path.ok_or_else(|| anyhow::anyhow!(USAGE))?,
+        format,
+    };
+    let theme = toml::to_string_pretty(&import.load()?)?;
+
+    if let Some(name) = name {
+        let dir = AmoebaConfig::themes_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{name}.toml"));
+        std::fs::write(&path, theme)?;
+        println!("Wrote theme to {}", path.display());
+    } else {
+        print!("{theme}");
     }
 
     Ok(())
@@ -0,0 +1,207 @@
+use crate::query::Engine;
+use crate::theme::Theme;
+use egui::Ui;
+
+#[derive(Debug, Default)]
+pub struct EnginePicker {
+    pub active: usize,
+}
+
+impl EnginePicker {
+    // Of `engines`, those matching a query bar that is still being typed as a prefix, best
+    // first. Empty once the bar contains anything but a single `@word`.
+    pub fn matches(query_bar: &str, engines: Vec<Engine>) -> Vec<Engine> {
+        let Some(pattern) = query_bar.strip_prefix('@') else {
+            return Vec::new();
+        };
+        if pattern.contains(char::is_whitespace) {
+            return Vec::new();
+        }
+
+        let mut scored: Vec<_> = engines
+            .into_iter()
+            .filter_map(|engine| {
+                let prefix = engine.prefix().trim_start_matches('@');
+                let score = fuzzy_score(pattern, prefix)
+                    .map(|score| score * 2)
+                    .max(fuzzy_score(pattern, engine.name()))?;
+                Some((score, engine))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| -score);
+        scored.into_iter().map(|(_, engine)| engine).collect()
+    }
+
+    pub fn next(&mut self, len: usize) {
+        self.active = if self.active + 1 < len {
+            self.active + 1
+        } else {
+            0
+        };
+    }
+
+    pub fn prev(&mut self, len: usize) {
+        self.active = if self.active > 0 {
+            self.active - 1
+        } else {
+            len.saturating_sub(1)
+        };
+    }
+
+    // Returns the index of a clicked row.
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        theme: &Theme,
+        width: f32,
+        engines: &[Engine],
+    ) -> Option<usize> {
+        self.active = self.active.min(engines.len().saturating_sub(1));
+        let mut clicked = None;
+
+        for (i, engine) in engines.iter().enumerate() {
+            let id = ui
+                .id()
+                .with(("engine_picker", engine.prefix(), engine.name()));
+            let is_hovered = ui.ctx().read_response(id).is_some_and(|r| r.hovered());
+
+            let fill = if is_hovered {
+                theme.hovered_bg_fill
+            } else if i == self.active {
+                theme.active_bg_fill
+            } else {
+                theme.window_fill
+            };
+            let frame = theme.row_frame(fill, i == engines.len() - 1);
+
+            let rect = frame
+                .show(ui, |ui| {
+                    ui.set_width(width - (theme.margin.left + theme.margin.right) as f32);
+                    ui.horizontal(|ui| {
+                        engine.icon().ui(ui);
+                        ui.monospace(egui::RichText::new(engine.prefix()).strong());
+                        ui.label(engine.name());
+                        ui.weak(engine.description());
+                    });
+                })
+                .response
+                .rect;
+
+            let response = ui.interact(rect, id, egui::Sense::click());
+            if response.hovered() && ui.input(|i| i.pointer.is_moving()) {
+                self.active = i;
+            }
+            if response.clicked() {
+                clicked = Some(i);
+            }
+        }
+
+        clicked
+    }
+}
+
+// Case-insensitive subsequence match, favouring consecutive characters and early matches.
+fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i32> {
+    let mut candidate = candidate.chars().flat_map(char::to_lowercase).enumerate();
+    let mut score = 0;
+    let mut last = None;
+
+    for p in pattern.chars().flat_map(char::to_lowercase) {
+        let (i, _) = candidate.find(|&(_, c)| c == p)?;
+        score += match last {
+            Some(last) if i == last + 1 => 3,
+            None if i == 0 => 3,
+            _ => 1,
+        };
+        last = Some(i);
+    }
+
+    Some(score - last.unwrap_or_default() as i32 / 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::SearchEngine;
+    use crate::response::Icon;
+    use std::sync::Arc;
+
+    struct Named(&'static str, &'static str);
+
+    #[async_trait::async_trait]
+    impl SearchEngine for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn prefix(&self) -> &str {
+            self.1
+        }
+
+        fn description(&self) -> &str {
+            ""
+        }
+
+        fn icon(&self) -> Icon {
+            Icon::Glyph("N".into())
+        }
+    }
+
+    #[test]
+    fn scores_fuzzy_matches() {
+        for (pattern, candidate, score) in [
+            ("", "wiki", Some(0)),
+            // A leading or consecutive match scores 3, any other 1.
+            ("wiki", "wiki", Some(12)),
+            ("WI", "Wiki", Some(6)),
+            ("wk", "wiki", Some(4)),
+            // Less the last match's offset over 4.
+            ("a", "abc", Some(3)),
+            ("a", "xxxxxxxxa", Some(-1)),
+            ("ba", "abc", None),
+            ("z", "wiki", None),
+        ] {
+            assert_eq!(
+                fuzzy_score(pattern, candidate),
+                score,
+                "{pattern} in {candidate}"
+            );
+        }
+    }
+
+    #[test]
+    fn ranks_engines_by_prefix_then_name() {
+        let engines: Vec<Engine> = [
+            ("fzf", "@fzf"),
+            ("rga", "@rg"),
+            ("feeds", "@rss"),
+            ("selfhosted", "@self"),
+            ("wikipedia", "@wiki"),
+        ]
+        .into_iter()
+        .map(|(name, prefix)| Arc::new(Named(name, prefix)) as Engine)
+        .collect();
+        let names = |query_bar: &str| {
+            EnginePicker::matches(query_bar, engines.clone())
+                .iter()
+                .map(|engine| engine.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        for (query_bar, expected) in [
+            ("@", vec!["fzf", "rga", "feeds", "selfhosted", "wikipedia"]),
+            ("@rg", vec!["rga"]),
+            // Prefix matches count double over name matches.
+            ("@f", vec!["fzf", "feeds", "selfhosted"]),
+            // And earlier matches beat later ones.
+            ("@e", vec!["selfhosted", "feeds", "wikipedia"]),
+            ("@x", vec![]),
+            // Nothing once the prefix is complete or there's no prefix.
+            ("@fzf ", vec![]),
+            ("@f x", vec![]),
+            ("fzf", vec![]),
+        ] {
+            assert_eq!(names(query_bar), expected, "{query_bar}");
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::query::SearchRequest;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// A result as plain data, so it can be cached and turned into a `QueryResponse` again later.
+pub type ResultData = Arc<dyn Any + Send + Sync>;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct CachePolicy {
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    // Serving an entry older than this also refetches it in the background for next time.
+    pub revalidate_after_secs: Option<u64>,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        CachePolicy {
+            ttl_secs: 300,
+            max_entries: 100,
+            revalidate_after_secs: None,
+        }
+    }
+}
+
+// `default` applies to every cacheable engine unless overridden by name in `engines`.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub disabled: bool,
+    pub default: CachePolicy,
+    pub engines: HashMap<String, CachePolicy>,
+}
+
+impl CacheConfig {
+    pub fn policy(&self, engine: &str) -> Option<CachePolicy> {
+        (!self.disabled).then(|| *self.engines.get(engine).unwrap_or(&self.default))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    engine: String,
+    query: String,
+    args: Vec<String>,
+}
+
+impl CacheKey {
+    // Queries differing only in case or spacing share an entry. Everything else the engine
+    // sees besides the text (filter args, flags and modifiers) is part of the key.
+    pub fn new(engine: &str, request: &SearchRequest) -> Self {
+        let query = request
+            .text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+
+        let mut flags = request.flags.clone();
+        flags.sort();
+        let mut modifiers: Vec<_> = request
+            .query
+            .modifiers()
+            .map(|m| format!("{}:{}", m.key, m.value))
+            .collect();
+        modifiers.sort();
+
+        let args = request
+            .args
+            .iter()
+            .cloned()
+            .chain(flags.into_iter().map(|flag| format!("+{flag}")))
+            .chain(modifiers)
+            .collect();
+
+        CacheKey {
+            engine: engine.to_string(),
+            query,
+            args,
+        }
+    }
+}
+
+struct Entry {
+    results: Arc<Vec<ResultData>>,
+    fetched: Instant,
+    used: Instant,
+    revalidating: bool,
+}
+
+pub enum Lookup {
+    Miss,
+    Fresh(Arc<Vec<ResultData>>),
+    // Still servable, but the caller should refetch it in the background.
+    Stale(Arc<Vec<ResultData>>),
+}
+
+#[derive(Default)]
+pub struct ResultCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+}
+
+impl std::fmt::Debug for ResultCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResultCache")
+            .field("config", &self.config)
+            .field("entries", &self.entries.lock().len())
+            .finish()
+    }
+}
+
+impl ResultCache {
+    pub fn new(config: CacheConfig) -> Self {
+        ResultCache {
+            config,
+            entries: Mutex::default(),
+        }
+    }
+
+    pub fn enabled_for(&self, engine: &str) -> bool {
+        self.config.policy(engine).is_some()
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Lookup {
+        let Some(policy) = self.config.policy(&key.engine) else {
+            return Lookup::Miss;
+        };
+        let mut entries = self.entries.lock();
+        let Some(entry) = entries.get_mut(key) else {
+            return Lookup::Miss;
+        };
+
+        let age = entry.fetched.elapsed();
+        if age >= Duration::from_secs(policy.ttl_secs) {
+            entries.remove(key);
+            return Lookup::Miss;
+        }
+
+        entry.used = Instant::now();
+        match policy.revalidate_after_secs {
+            Some(secs) if age >= Duration::from_secs(secs) && !entry.revalidating => {
+                entry.revalidating = true;
+                Lookup::Stale(entry.results.clone())
+            }
+            _ => Lookup::Fresh(entry.results.clone()),
+        }
+    }
+
+    // Lets the next lookup of a stale entry try revalidating it again.
+    pub fn revalidation_failed(&self, key: &CacheKey) {
+        if let Some(entry) = self.entries.lock().get_mut(key) {
+            entry.revalidating = false;
+        }
+    }
+
+    // Stores `results`, evicting the engine's least recently used entries beyond its limit.
+    pub fn insert(&self, key: CacheKey, results: Arc<Vec<ResultData>>) {
+        let Some(policy) = self.config.policy(&key.engine) else {
+            return;
+        };
+        let mut entries = self.entries.lock();
+        let now = Instant::now();
+        let engine = key.engine.clone();
+        entries.insert(
+            key,
+            Entry {
+                results,
+                fetched: now,
+                used: now,
+                revalidating: false,
+            },
+        );
+
+        let mut keys: Vec<_> = entries
+            .iter()
+            .filter(|(key, _)| key.engine == engine)
+            .map(|(key, entry)| (entry.used, key.clone()))
+            .collect();
+        if keys.len() > policy.max_entries {
+            keys.sort_by_key(|(used, _)| *used);
+            for (_, key) in &keys[..keys.len() - policy.max_entries] {
+                entries.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::parser::Query;
+
+    fn key(engine: &str, query: &str) -> CacheKey {
+        let query = Query::parse(query);
+        CacheKey::new(engine, &SearchRequest::new(&query, "@wi"))
+    }
+
+    fn results(n: usize) -> Arc<Vec<ResultData>> {
+        Arc::new((0..n).map(|i| Arc::new(i) as ResultData).collect())
+    }
+
+    #[test]
+    fn normalizes_queries_but_not_args() {
+        assert_eq!(
+            key("wikipedia", "Rust  lang"),
+            key("wikipedia", " rust lang")
+        );
+        assert_eq!(
+            key("wikipedia", "@wi+text:de rust limit:5"),
+            key("wikipedia", "limit:5 @wi+text:de  RUST")
+        );
+        assert_ne!(
+            key("wikipedia", "@wi:de rust"),
+            key("wikipedia", "@wi:fr rust")
+        );
+        assert_ne!(key("wikipedia", "rust limit:5"), key("wikipedia", "rust"));
+        assert_ne!(key("wikipedia", "rust"), key("searxng", "rust"));
+    }
+
+    fn cache(policy: CachePolicy) -> ResultCache {
+        ResultCache::new(CacheConfig {
+            default: policy,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn expires_and_evicts_per_engine() {
+        let policy = CachePolicy {
+            ttl_secs: 60,
+            max_entries: 2,
+            revalidate_after_secs: None,
+        };
+        let cache = cache(policy);
+
+        cache.insert(key("wikipedia", "a"), results(1));
+        cache.insert(key("searxng", "a"), results(1));
+        cache.insert(key("wikipedia", "b"), results(2));
+        // Touch `a` so that `b` is the least recently used.
+        assert!(matches!(cache.get(&key("wikipedia", "a")), Lookup::Fresh(r) if r.len() == 1));
+        cache.insert(key("wikipedia", "c"), results(3));
+
+        assert!(matches!(cache.get(&key("wikipedia", "b")), Lookup::Miss));
+        assert!(matches!(
+            cache.get(&key("wikipedia", "c")),
+            Lookup::Fresh(_)
+        ));
+        assert!(matches!(cache.get(&key("searxng", "a")), Lookup::Fresh(_)));
+
+        let expiring = self::cache(CachePolicy {
+            ttl_secs: 0,
+            ..policy
+        });
+        expiring.insert(key("wikipedia", "a"), results(1));
+        assert!(matches!(expiring.get(&key("wikipedia", "a")), Lookup::Miss));
+    }
+
+    #[test]
+    fn revalidates_stale_entries_once() {
+        let cache = cache(CachePolicy {
+            revalidate_after_secs: Some(0),
+            ..Default::default()
+        });
+
+        cache.insert(key("wikipedia", "a"), results(1));
+        assert!(matches!(
+            cache.get(&key("wikipedia", "a")),
+            Lookup::Stale(_)
+        ));
+        assert!(matches!(
+            cache.get(&key("wikipedia", "a")),
+            Lookup::Fresh(_)
+        ));
+        cache.insert(key("wikipedia", "a"), results(2));
+        assert!(matches!(cache.get(&key("wikipedia", "a")), Lookup::Stale(r) if r.len() == 2));
+
+        // A failed revalidation is retried on the next lookup.
+        cache.revalidation_failed(&key("wikipedia", "a"));
+        assert!(matches!(cache.get(&key("wikipedia", "a")), Lookup::Stale(r) if r.len() == 2));
+        assert!(matches!(
+            cache.get(&key("wikipedia", "a")),
+            Lookup::Fresh(_)
+        ));
+
+        let disabled = ResultCache::new(CacheConfig {
+            disabled: true,
+            ..Default::default()
+        });
+        disabled.insert(key("wikipedia", "a"), results(1));
+        assert!(matches!(disabled.get(&key("wikipedia", "a")), Lookup::Miss));
+    }
+}
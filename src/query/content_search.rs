@@ -1,11 +1,16 @@
-use crate::query::SearchEngine;
-use crate::response::QueryResponse;
-use egui::Ui;
+use crate::config::expand_home;
+use crate::query::{SearchEngine, SearchRequest};
+use crate::response::{ActionKind, Field, Icon, Payload, QueryResponse, ResultItem};
 use flume::Sender;
 use futures::{AsyncBufReadExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::PathBuf;
 
-#[derive(Debug)]
+const ICON: &str = "󰈞";
+const COPY_LOCATION: &str = "copy_location";
+
+#[derive(Debug, Default)]
 pub struct Rga;
 
 impl Rga {
@@ -34,6 +39,23 @@ pub struct RgaMatchData {
     submatches: Vec<Match>,
 }
 
+// The payload of a response: the match and the full path of the file it's in.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Hit {
+    path: PathBuf,
+    data: RgaMatchData,
+}
+
+impl Hit {
+    // `path:line`, as editors and compilers print locations.
+    fn location(&self) -> String {
+        match self.data.line_number {
+            Some(line) => format!("{}:{line}", self.path.display()),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Match {
     #[serde(rename = "match")]
@@ -49,25 +71,43 @@ pub struct Text {
 
 #[async_trait::async_trait]
 impl SearchEngine for Rga {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "rga"
     }
 
-    fn prefix(&self) -> &'static str {
+    fn prefix(&self) -> &str {
         "@rg"
     }
 
-    fn icon(&self) -> Box<dyn Fn(&mut Ui) -> egui::Response + Send> {
-        Box::new(|ui| ui.monospace("󰈞"))
+    fn description(&self) -> &str {
+        "Search file contents with ripgrep-all"
+    }
+
+    fn icon(&self) -> Icon {
+        Icon::Glyph(ICON.into())
     }
 
-    async fn search(&self, query: &str, channel: Sender<QueryResponse>) -> anyhow::Result<()> {
-        let dir = std::env::current_dir().unwrap_or_default();
+    async fn search(
+        &self,
+        request: &SearchRequest,
+        channel: Sender<QueryResponse>,
+    ) -> anyhow::Result<()> {
+        let query = request.text.as_str();
+        let dir = match &request.args {
+            Some(dir) => expand_home(dir.as_ref()),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
         log::info!("FileSystem Query: {}, cwd: {:?}", query, dir);
 
-        let mut child = async_process::Command::new("rga")
+        let mut command = async_process::Command::new("rga");
+        if let Some(ext) = request.modifier("ext") {
+            command.arg("--glob").arg(format!("*.{ext}"));
+        }
+
+        let mut child = command
             .arg("--json")
             .arg(query)
+            .current_dir(&dir)
             .stdout(async_process::Stdio::piped())
             .kill_on_drop(true)
             .spawn()?;
@@ -83,52 +123,40 @@ impl SearchEngine for Rga {
                 RgaJson::Irrelevant => continue,
                 RgaJson::Match { data } => data,
             };
-            let rga_match_clone = rga_match.clone();
-
-            let icon = self.icon();
-
-            let send_res = channel
-                .send_async(QueryResponse::new(
-                    {
-                        Box::new(move |ui: &mut egui::Ui| {
-                            icon(ui);
-
-                            ui.add(
-                                egui::Label::new(
-                                    egui::RichText::new(format!(
-                                        "./{path}:",
-                                        path = rga_match.path.text
-                                    ))
-                                    .monospace()
-                                    .italics(),
-                                )
-                                .wrap_mode(egui::TextWrapMode::Wrap),
-                            );
-
-                            ui.add(
-                                egui::Label::new(
-                                    egui::RichText::new(format!(
-                                        "{match}",
-                                        r#match = rga_match.lines.text.trim()
-                                    ))
-                                    .monospace(),
-                                )
-                                .wrap_mode(egui::TextWrapMode::Wrap),
-                            )
-                        })
-                    },
-                    {
-                        let dir = dir.clone().to_string_lossy().to_string();
-                        Box::new(move |ui: &mut egui::Ui| {
-                            ui.ctx().send_cmd(egui::OutputCommand::CopyText(format!(
-                                "{dir}/{path}",
-                                path = rga_match_clone.path.text
-                            )));
-                        })
-                    },
-                    5 - (i as i64),
-                ))
-                .await;
+            let full_path = dir.join(&rga_match.path.text);
+            let line = rga_match.lines.text.trim();
+            let mut item = ResultItem::new(
+                Icon::Glyph(ICON.to_string()),
+                format!("./{}", rga_match.path.text),
+            )
+            .with_subtitle(line)
+            .with_metadata("path", full_path.to_string_lossy())
+            .with_action(
+                "Copy path",
+                ActionKind::Copy(full_path.to_string_lossy().to_string()),
+            )
+            .with_action("Open", ActionKind::OpenPath(full_path.clone()));
+            if let Some(line_number) = rga_match.line_number {
+                item = item
+                    .with_metadata("line", line_number.to_string())
+                    .with_action(
+                        "Copy location",
+                        ActionKind::Engine(COPY_LOCATION.to_string()),
+                    );
+            }
+            for submatch in &rga_match.submatches {
+                if let Some(range) = char_range(&rga_match.lines.text, submatch) {
+                    item = item.with_match(Field::Subtitle, range);
+                }
+            }
+
+            let hit = Hit {
+                path: full_path,
+                data: rga_match,
+            };
+            let response = QueryResponse::new(item, 5 - (i as i64)).with_payload(Payload::new(hit));
+
+            let send_res = channel.send_async(response).await;
 
             if let Err(err) = send_res {
                 return Err(anyhow::anyhow!("Err: {}", err));
@@ -137,4 +165,57 @@ impl SearchEngine for Rga {
 
         Ok(())
     }
+
+    fn run_action(
+        &self,
+        action: &str,
+        payload: Option<&Payload>,
+    ) -> anyhow::Result<Option<ActionKind>> {
+        let hit = payload
+            .and_then(Payload::downcast_ref::<Hit>)
+            .ok_or_else(|| anyhow::anyhow!("No match to run {action} on"))?;
+        match action {
+            COPY_LOCATION => Ok(Some(ActionKind::Copy(hit.location()))),
+            _ => anyhow::bail!("rga has no action {action}"),
+        }
+    }
+}
+
+// The chars `submatch` spans in the trimmed line, given its byte offsets into `line`.
+fn char_range(line: &str, submatch: &Match) -> Option<Range<usize>> {
+    let lead = line.len() - line.trim_start().len();
+    let trimmed = line.trim();
+    let start = (submatch.start as usize)
+        .checked_sub(lead)?
+        .min(trimmed.len());
+    let end = (submatch.end as usize)
+        .saturating_sub(lead)
+        .min(trimmed.len());
+    let chars = |bytes: usize| trimmed.get(..bytes).map(|s| s.chars().count());
+    Some(chars(start)?..chars(end)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submatch(start: u64, end: u64) -> Match {
+        Match {
+            mat: Text {
+                text: String::new(),
+            },
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn converts_submatches_to_trimmed_char_ranges() {
+        let line = "    let café = \"crème\";\n";
+        // `café` and `crème` as byte offsets into the untrimmed line.
+        assert_eq!(char_range(line, &submatch(8, 13)), Some(4..8));
+        assert_eq!(char_range(line, &submatch(17, 23)), Some(12..17));
+        // A match inside the trimmed indentation has nothing to highlight.
+        assert_eq!(char_range(line, &submatch(0, 2)), None);
+    }
 }
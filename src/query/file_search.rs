@@ -1,10 +1,12 @@
-use crate::query::SearchEngine;
-use crate::response::QueryResponse;
-use egui::Ui;
+use crate::config::expand_home;
+use crate::query::{SearchEngine, SearchRequest};
+use crate::response::{ActionKind, Icon, Payload, QueryResponse, ResultItem};
 use flume::Sender;
 use futures::{AsyncBufReadExt, StreamExt};
 
-#[derive(Debug)]
+const ICON: &str = "";
+
+#[derive(Debug, Default)]
 pub struct Fzf;
 
 impl Fzf {
@@ -15,25 +17,45 @@ impl Fzf {
 
 #[async_trait::async_trait]
 impl SearchEngine for Fzf {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "fzf"
     }
 
-    fn prefix(&self) -> &'static str {
+    fn prefix(&self) -> &str {
         "@fzf"
     }
 
-    fn icon(&self) -> Box<dyn Fn(&mut Ui) -> egui::Response + Send> {
-        Box::new(|ui| ui.monospace(""))
+    fn description(&self) -> &str {
+        "Fuzzy find files"
+    }
+
+    fn icon(&self) -> Icon {
+        Icon::Glyph("".into())
     }
 
-    async fn search(&self, query: &str, channel: Sender<QueryResponse>) -> anyhow::Result<()> {
-        let dir = std::env::current_dir().unwrap_or_default();
+    async fn search(
+        &self,
+        request: &SearchRequest,
+        channel: Sender<QueryResponse>,
+    ) -> anyhow::Result<()> {
+        let dir = match &request.args {
+            Some(dir) => expand_home(dir.as_ref()),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        // fzf splits its pattern on spaces, so escape the ones inside phrases.
+        let query = request
+            .query
+            .terms()
+            .map(|term| term.text.replace(' ', "\\ "))
+            .chain(request.modifier("ext").map(|ext| format!(".{ext}$")))
+            .collect::<Vec<_>>()
+            .join(" ");
         log::info!("FileSystem Query: {}, cwd: {:?}", query, dir);
 
         let mut child = async_process::Command::new("fzf")
             .arg("-f")
-            .arg(query)
+            .arg(&query)
+            .current_dir(&dir)
             .stdout(async_process::Stdio::piped())
             .kill_on_drop(true)
             .spawn()?;
@@ -43,37 +65,18 @@ impl SearchEngine for Fzf {
 
         while let Some((i, path)) = lines.next().await {
             let path = path?;
-            let path_clone = path.clone();
-
-            let icon = self.icon();
-
-            let send_res = channel
-                .send_async(QueryResponse::new(
-                    {
-                        Box::new(move |ui: &mut egui::Ui| {
-                            icon(ui);
+            let full_path = dir.join(&path);
+            let item = ResultItem::new(Icon::Glyph(ICON.to_string()), format!("./{path}"))
+                .with_metadata("path", full_path.to_string_lossy())
+                .with_action(
+                    "Copy path",
+                    ActionKind::Copy(full_path.to_string_lossy().to_string()),
+                )
+                .with_action("Open", ActionKind::OpenPath(full_path.clone()));
+            let response =
+                QueryResponse::new(item, 5 - (i as i64)).with_payload(Payload::new(full_path));
 
-                            ui.add(
-                                egui::Label::new(
-                                    egui::RichText::new(format!("./{path}"))
-                                        .monospace()
-                                        .italics(),
-                                )
-                                .wrap_mode(egui::TextWrapMode::Wrap),
-                            )
-                        })
-                    },
-                    {
-                        let dir = dir.clone().to_string_lossy().to_string();
-                        Box::new(move |ui: &mut egui::Ui| {
-                            ui.ctx().send_cmd(egui::OutputCommand::CopyText(format!(
-                                "{dir}/{path_clone}"
-                            )));
-                        })
-                    },
-                    5 - (i as i64),
-                ))
-                .await;
+            let send_res = channel.send_async(response).await;
 
             if let Err(err) = send_res {
                 return Err(anyhow::anyhow!("Err: {}", err));
@@ -0,0 +1,447 @@
+use http_client::isahc::IsahcClient;
+use isahc::config::Configurable;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use surf::{Client, Config, Request, Response, StatusCode, Url};
+
+pub const USER_AGENT: &str = "Amoeba (me@arvinsk.org)";
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Shared by all network engines; each engine gets its own rate limit and circuit breaker.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HttpConfig {
+    // Overrides HTTPS_PROXY/HTTP_PROXY, e.g. `http://proxy:3128` or `socks5://localhost:1080`.
+    pub proxy: Option<String>,
+    // Sustained requests per second, allowing bursts of up to `burst`. Zero disables the limit.
+    pub requests_per_sec: f64,
+    pub burst: u32,
+    // Retries for network errors, 429, 500 and 502-504, waiting `backoff_ms`, then twice that, and so
+    // on, or as long as `Retry-After` asks, unless that's longer than `max_backoff_ms`.
+    pub max_retries: u32,
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // After this many failed requests in a row, the engine is considered offline and not
+    // tried again for `offline_secs`.
+    pub failure_threshold: u32,
+    pub offline_secs: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            proxy: None,
+            requests_per_sec: 2.,
+            burst: 4,
+            max_retries: 2,
+            backoff_ms: 500,
+            max_backoff_ms: 10_000,
+            failure_threshold: 3,
+            offline_secs: 30,
+        }
+    }
+}
+
+impl HttpConfig {
+    fn proxy_for(&self, url: &Url) -> Option<String> {
+        let vars: &[&str] = match url.scheme() {
+            "https" => &["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"],
+            _ => &["HTTP_PROXY", "http_proxy"],
+        };
+        self.proxy.clone().or_else(|| {
+            vars.iter()
+                .find_map(|var| std::env::var(var).ok())
+                .filter(|proxy| !proxy.is_empty())
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("offline, retrying in {} s", .retry_in.as_secs().max(1))]
+    Offline { retry_in: Duration },
+}
+
+// A client for `base_url` that identifies itself, gives up after `timeout` and goes through
+// the configured proxy. Returns `None`, after logging why, if anything is invalid.
+pub fn client(
+    base_url: &str,
+    timeout: Duration,
+    headers: &BTreeMap<String, String>,
+    http: &HttpConfig,
+) -> Option<Client> {
+    let url = Url::parse(base_url)
+        .inspect_err(|e| log::error!("Invalid base URL {base_url}: {e}"))
+        .ok()?;
+
+    let mut config = Config::new()
+        .set_timeout(Some(timeout))
+        .add_header("User-Agent", USER_AGENT)
+        .inspect_err(|e| log::error!("{e}"))
+        .ok()?;
+
+    if let Some(proxy) = http.proxy_for(&url) {
+        let uri = proxy
+            .parse::<isahc::http::Uri>()
+            .inspect_err(|e| log::error!("Invalid proxy {proxy}: {e}"))
+            .ok()?;
+        let no_proxy = std::env::var("NO_PROXY")
+            .or_else(|_| std::env::var("no_proxy"))
+            .unwrap_or_default();
+        let client = isahc::HttpClient::builder()
+            .timeout(timeout)
+            .proxy(Some(uri))
+            .proxy_blacklist(no_proxy.split(',').map(str::trim).filter(|h| !h.is_empty()))
+            .build()
+            .inspect_err(|e| log::error!("{e}"))
+            .ok()?;
+        config = config.set_http_client(IsahcClient::from_client(client));
+    }
+
+    for (name, value) in headers {
+        config = config
+            .add_header(name.as_str(), value.as_str())
+            .inspect_err(|e| log::error!("Invalid header {name}: {e}"))
+            .ok()?;
+    }
+
+    config
+        .set_base_url(url)
+        .try_into()
+        .inspect_err(|e| log::error!("{e}"))
+        .ok()
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+    // Whether a request is probing the connection after the cooldown.
+    probing: bool,
+}
+
+// Held by the request probing the connection, letting the next one probe once it's done,
+// however it ends.
+struct Probe<'a>(&'a Mutex<Circuit>);
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        self.0.lock().probing = false;
+    }
+}
+
+// Rate limiting, retries and circuit breaking around an engine's requests.
+#[derive(Debug)]
+pub struct HttpPolicy {
+    config: HttpConfig,
+    bucket: Mutex<Bucket>,
+    circuit: Mutex<Circuit>,
+}
+
+impl HttpPolicy {
+    pub fn new(config: &HttpConfig) -> Self {
+        HttpPolicy {
+            config: config.clone(),
+            bucket: Mutex::new(Bucket {
+                tokens: config.burst.max(1) as f64,
+                refilled: Instant::now(),
+            }),
+            circuit: Mutex::default(),
+        }
+    }
+
+    // Waits until the rate limit allows another request.
+    async fn acquire(&self) {
+        let rate = self.config.requests_per_sec;
+        if rate <= 0. {
+            return;
+        }
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock();
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+                bucket.tokens =
+                    (bucket.tokens + elapsed * rate).min(self.config.burst.max(1) as f64);
+                bucket.refilled = now;
+                if bucket.tokens >= 1. {
+                    bucket.tokens -= 1.;
+                    return;
+                }
+                Duration::from_secs_f64((1. - bucket.tokens) / rate)
+            };
+            futures_timer::Delay::new(wait).await;
+        }
+    }
+
+    fn check_circuit(&self) -> Result<Option<Probe<'_>>, HttpError> {
+        let mut circuit = self.circuit.lock();
+        match circuit.open_until {
+            Some(until) if until > Instant::now() => Err(HttpError::Offline {
+                retry_in: until - Instant::now(),
+            }),
+            // Past the cooldown, a single request is let through to probe the connection.
+            Some(_) if circuit.probing => Err(HttpError::Offline {
+                retry_in: Duration::ZERO,
+            }),
+            Some(_) => {
+                circuit.probing = true;
+                Ok(Some(Probe(&self.circuit)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Returns the error to report, which is `Offline` once the circuit opens.
+    fn record_failure(&self, error: anyhow::Error) -> anyhow::Error {
+        let mut circuit = self.circuit.lock();
+        circuit.failures += 1;
+        if circuit.failures < self.config.failure_threshold.max(1) {
+            return error;
+        }
+
+        log::warn!("Going offline after {} failures: {error}", circuit.failures);
+        let retry_in = Duration::from_secs(self.config.offline_secs);
+        circuit.open_until = Some(Instant::now() + retry_in);
+        HttpError::Offline { retry_in }.into()
+    }
+
+    fn record_success(&self) {
+        let mut circuit = self.circuit.lock();
+        circuit.failures = 0;
+        circuit.open_until = None;
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .config
+            .backoff_ms
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.config.max_backoff_ms);
+        Duration::from_millis(delay)
+    }
+
+    // Sends `request` through `send`, which performs a single attempt; it's given a fresh
+    // clone of the request for every retry.
+    pub async fn send<F, Fut>(&self, request: Request, send: F) -> anyhow::Result<Response>
+    where
+        F: Fn(Request) -> Fut,
+        Fut: Future<Output = anyhow::Result<Response>>,
+    {
+        let _probe = self.check_circuit()?;
+
+        let mut attempt = 0;
+        loop {
+            self.acquire().await;
+            let result = send(request.clone()).await;
+            let retryable = match &result {
+                Ok(response) => is_retryable(response.status()),
+                Err(_) => true,
+            };
+            if !retryable {
+                self.record_success();
+                return result;
+            }
+
+            let delay = match &result {
+                Ok(response) => retry_after(response).unwrap_or_else(|| self.backoff(attempt)),
+                Err(_) => self.backoff(attempt),
+            };
+            let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+            if attempt >= self.config.max_retries || delay > max_backoff {
+                return match result {
+                    // Being rate limited means the server is reachable.
+                    Ok(response) if response.status() == StatusCode::TooManyRequests => {
+                        Ok(response)
+                    }
+                    Ok(response) => {
+                        Err(self
+                            .record_failure(anyhow::anyhow!("server error {}", response.status())))
+                    }
+                    Err(e) => Err(self.record_failure(e)),
+                };
+            }
+
+            log::info!("Retrying in {delay:?} (attempt {})", attempt + 1);
+            futures_timer::Delay::new(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TooManyRequests
+            | StatusCode::InternalServerError
+            | StatusCode::BadGateway
+            | StatusCode::ServiceUnavailable
+            | StatusCode::GatewayTimeout
+    )
+}
+
+// `Retry-After` as either a number of seconds or an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    parse_retry_after(response.header("Retry-After")?.last().as_str())
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = jiff::fmt::rfc2822::DateTimeParser::new()
+        .parse_timestamp(value)
+        .ok()?;
+    Some(
+        jiff::Timestamp::now()
+            .duration_until(date)
+            .try_into()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::test_server::{Reply, in_turn, serve};
+    use std::sync::atomic::Ordering;
+
+    fn policy(config: HttpConfig) -> (HttpPolicy, Client) {
+        (HttpPolicy::new(&config), Client::new())
+    }
+
+    async fn get(policy: &HttpPolicy, client: &Client, url: &str) -> anyhow::Result<Response> {
+        let request = client.get(url).build();
+        policy
+            .send(request, |request| async {
+                client.send(request).await.map_err(|e| anyhow::anyhow!(e))
+            })
+            .await
+    }
+
+    #[test]
+    fn retries_honoring_retry_after() {
+        futures::executor::block_on(async {
+            let (url, calls) = serve(in_turn(vec![
+                Reply::new("429 Too Many Requests").with_header("Retry-After", "0"),
+                Reply::new("503 Service Unavailable"),
+                Reply::new("200 OK"),
+            ]));
+            let (policy, client) = policy(HttpConfig {
+                backoff_ms: 1,
+                ..Default::default()
+            });
+
+            let response = get(&policy, &client, &url).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+            // A `Retry-After` beyond the longest backoff is passed back instead of waited on.
+            let (url, calls) = serve(in_turn(vec![
+                Reply::new("429 Too Many Requests").with_header("Retry-After", "3600"),
+            ]));
+            let response = get(&policy, &client, &url).await.unwrap();
+            assert_eq!(response.status(), StatusCode::TooManyRequests);
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    fn goes_offline_after_repeated_failures() {
+        futures::executor::block_on(async {
+            let (url, calls) = serve(in_turn(vec![Reply::new("503 Service Unavailable")]));
+            let (policy, client) = policy(HttpConfig {
+                max_retries: 0,
+                failure_threshold: 2,
+                ..Default::default()
+            });
+
+            let first = get(&policy, &client, &url).await.unwrap_err();
+            assert!(first.downcast_ref::<HttpError>().is_none(), "{first}");
+            let second = get(&policy, &client, &url).await.unwrap_err();
+            assert!(matches!(
+                second.downcast_ref(),
+                Some(HttpError::Offline { .. })
+            ));
+
+            // While offline, requests fail without reaching the server.
+            let third = get(&policy, &client, &url).await.unwrap_err();
+            assert!(matches!(
+                third.downcast_ref(),
+                Some(HttpError::Offline { .. })
+            ));
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn probes_one_request_at_a_time() {
+        futures::executor::block_on(async {
+            let (url, calls) = serve(in_turn(vec![
+                Reply::new("503 Service Unavailable"),
+                Reply::new("200 OK"),
+            ]));
+            let (policy, client) = policy(HttpConfig {
+                max_retries: 0,
+                failure_threshold: 1,
+                offline_secs: 0,
+                ..Default::default()
+            });
+            assert!(get(&policy, &client, &url).await.is_err());
+
+            // Past the cooldown, requests while another probes are turned away.
+            let probe = policy.check_circuit().unwrap();
+            assert!(probe.is_some());
+            assert!(matches!(
+                get(&policy, &client, &url)
+                    .await
+                    .unwrap_err()
+                    .downcast_ref(),
+                Some(HttpError::Offline { .. })
+            ));
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+            drop(probe);
+            let response = get(&policy, &client, &url).await.unwrap();
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert!(policy.check_circuit().unwrap().is_none());
+        });
+    }
+
+    #[test]
+    fn retries_transient_statuses() {
+        for status in [
+            StatusCode::TooManyRequests,
+            StatusCode::InternalServerError,
+            StatusCode::BadGateway,
+            StatusCode::ServiceUnavailable,
+            StatusCode::GatewayTimeout,
+        ] {
+            assert!(is_retryable(status), "{status}");
+        }
+        assert!(!is_retryable(StatusCode::NotImplemented));
+        assert!(!is_retryable(StatusCode::NotFound));
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
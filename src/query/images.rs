@@ -0,0 +1,219 @@
+use crate::query::http::{self, DEFAULT_TIMEOUT, HttpConfig};
+use egui::{ColorImage, Context, TextureHandle, Ui, Vec2};
+use futures::executor::ThreadPool;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use surf::Client;
+
+/// Sets up the loader that results with an image icon shown in `ctx` are drawn with.
+/// Without one, they show their fallback glyph.
+pub fn install(ctx: &Context, config: &HttpConfig) {
+    // Image URLs are absolute, so the base URL only decides which proxy applies.
+    let loader = http::client(
+        "https://localhost",
+        DEFAULT_TIMEOUT,
+        &BTreeMap::new(),
+        config,
+    )
+    .and_then(|client| {
+        ImageLoader::new(client)
+            .inspect_err(|e| log::error!("Failed to start image loader: {e}"))
+            .ok()
+            .map(Arc::new)
+    });
+    ctx.data_mut(|data| data.insert_temp(loader_id(), loader));
+}
+
+/// The loader installed in `ctx`, if any.
+pub fn loader(ctx: &Context) -> Option<Arc<ImageLoader>> {
+    ctx.data(|data| data.get_temp::<Option<Arc<ImageLoader>>>(loader_id()))
+        .flatten()
+}
+
+fn loader_id() -> egui::Id {
+    egui::Id::new("amoeba-image-loader")
+}
+
+#[derive(Clone)]
+enum ImageState {
+    Loading,
+    Loaded(TextureHandle),
+    Failed,
+}
+
+/// Fetches remote images in the background and keeps them as textures, keyed by URL.
+pub struct ImageLoader {
+    client: Client,
+    pool: ThreadPool,
+    cache: Arc<Mutex<HashMap<String, ImageState>>>,
+}
+
+impl std::fmt::Debug for ImageLoader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageLoader")
+            .field("client", &self.client)
+            .field("cache", &"..")
+            .finish()
+    }
+}
+
+impl ImageLoader {
+    pub fn new(client: Client) -> anyhow::Result<Self> {
+        Ok(ImageLoader {
+            client,
+            pool: ThreadPool::builder()
+                .pool_size(2)
+                .name_prefix("amoeba-images-")
+                .create()?,
+            cache: Arc::default(),
+        })
+    }
+
+    // Where the image at `url` is at, starting the download on first use.
+    fn state(&self, ctx: &Context, url: &str) -> ImageState {
+        let url = normalize_url(url);
+        let mut cache = self.cache.lock();
+        if let Some(state) = cache.get(&url) {
+            return state.clone();
+        }
+        cache.insert(url.clone(), ImageState::Loading);
+
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+        let ctx = ctx.clone();
+        self.pool.spawn_ok(async move {
+            let state = match fetch(&client, &url).await {
+                Ok(image) => ImageState::Loaded(ctx.load_texture(&url, image, Default::default())),
+                Err(e) => {
+                    log::warn!("Failed to load image {url}: {e}");
+                    ImageState::Failed
+                }
+            };
+            cache.lock().insert(url, state);
+            ctx.request_repaint();
+        });
+
+        ImageState::Loading
+    }
+
+    /// Shows the image scaled to fit `max_size`, reserving its space while it loads.
+    /// Returns `None` if the image could not be loaded.
+    pub fn show(&self, ui: &mut Ui, url: &str, max_size: Vec2) -> Option<egui::Response> {
+        match self.state(ui.ctx(), url) {
+            ImageState::Loaded(texture) => {
+                let size = texture.size_vec2();
+                let scale = (max_size.x / size.x).min(max_size.y / size.y).min(1.);
+                Some(ui.image((texture.id(), size * scale)))
+            }
+            ImageState::Loading => Some(ui.allocate_exact_size(max_size, egui::Sense::hover()).1),
+            ImageState::Failed => None,
+        }
+    }
+}
+
+// Wikimedia hands out protocol-relative URLs like `//upload.wikimedia.org/...`.
+fn normalize_url(url: &str) -> String {
+    match url.strip_prefix("//") {
+        Some(rest) => format!("https://{rest}"),
+        None => url.to_string(),
+    }
+}
+
+/// Downloads and decodes the image at `url`.
+pub async fn fetch(client: &Client, url: &str) -> anyhow::Result<ColorImage> {
+    let mut response = client.get(url).await.map_err(|err| anyhow::anyhow!(err))?;
+    if !response.status().is_success() {
+        anyhow::bail!("HTTP {}", response.status());
+    }
+
+    let bytes = response
+        .body_bytes()
+        .await
+        .map_err(|err| anyhow::anyhow!(err))?;
+    let image = image::load_from_memory(&bytes)?.to_rgba8();
+
+    Ok(ColorImage::from_rgba_unmultiplied(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::test_server::{Reply, in_turn, serve};
+    use std::time::{Duration, Instant};
+
+    // Serves `body` with `status` to every request, at the URL of an image.
+    fn serve_image(status: &str, content_type: &str, body: Vec<u8>) -> String {
+        let reply = Reply::new(status)
+            .with_header("Content-Type", content_type)
+            .with_body(body);
+        format!("{}/thumb.png", serve(in_turn(vec![reply])).0)
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn fetch_decodes_png() {
+        let url = serve_image("200 OK", "image/png", png(3, 2));
+        let image = futures::executor::block_on(fetch(&Client::new(), &url)).unwrap();
+        assert_eq!(image.size, [3, 2]);
+        assert_eq!(image.pixels[0], egui::Color32::RED);
+    }
+
+    #[test]
+    fn fetch_rejects_errors_and_garbage() {
+        let missing = serve_image("404 Not Found", "text/plain", b"missing".to_vec());
+        assert!(futures::executor::block_on(fetch(&Client::new(), &missing)).is_err());
+
+        let garbage = serve_image("200 OK", "image/png", b"not an image".to_vec());
+        assert!(futures::executor::block_on(fetch(&Client::new(), &garbage)).is_err());
+    }
+
+    #[test]
+    fn loader_caches_textures() {
+        let url = serve_image("200 OK", "image/png", png(4, 4));
+        let loader = ImageLoader::new(Client::new()).unwrap();
+        let ctx = Context::default();
+
+        let texture = |loader: &ImageLoader| match loader.state(&ctx, &url) {
+            ImageState::Loaded(texture) => Some(texture),
+            ImageState::Loading | ImageState::Failed => None,
+        };
+
+        assert!(texture(&loader).is_none());
+        let start = Instant::now();
+        let first = loop {
+            if let Some(texture) = texture(&loader) {
+                break texture;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "image never loaded"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(first.size(), [4, 4]);
+        assert_eq!(texture(&loader).unwrap().id(), first.id());
+    }
+
+    #[test]
+    fn normalizes_protocol_relative_urls() {
+        assert_eq!(
+            normalize_url("//upload.wikimedia.org/a.jpg"),
+            "https://upload.wikimedia.org/a.jpg"
+        );
+        assert_eq!(
+            normalize_url("http://localhost/a.png"),
+            "http://localhost/a.png"
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use crate::query::cache::ResultData;
+use crate::query::http::{self, DEFAULT_TIMEOUT, HttpConfig, HttpPolicy};
+use crate::query::{SearchEngine, SearchRequest};
+use crate::response::{ActionKind, Icon, Payload, QueryResponse, ResultItem};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use strfmt::strfmt;
+use surf::Url;
+
+// JSON pointers (RFC 6901) into each result, e.g. `/title` or `/meta/score`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct JsonFields {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub url: Option<String>,
+    pub score: Option<String>,
+}
+
+impl Default for JsonFields {
+    fn default() -> Self {
+        JsonFields {
+            title: "/title".to_string(),
+            subtitle: None,
+            url: Some("/url".to_string()),
+            score: None,
+        }
+    }
+}
+
+// A search API described entirely in the config, e.g. for SearXNG:
+//
+//   [[query_config.json_engines]]
+//   name = "searxng"
+//   prefix = "@sx"
+//   base_url = "https://searx.example.org"
+//   path = "/search"
+//   query = { q = "{query}", format = "json" }
+//   results = "/results"
+//   fields = { title = "/title", subtitle = "/content", url = "/url", score = "/score" }
+//
+// `path` and `query` values are templates over `{query}` and `{args}` (the engine's filter
+// arguments, `@sx:args`), which are percent-encoded in either. `open_url` is a template over
+// the mapped `{title}`, `{subtitle}`, `{url}` and `{score}`, percent-encoded so they fit
+// anywhere in a URL, or as they are with a `_raw` suffix (e.g. `{url_raw}`). It defaults to
+// the result's URL.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct JsonEngineConfig {
+    pub name: String,
+    pub prefix: String,
+    pub description: String,
+    pub icon: String,
+    pub base_url: String,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+    pub results: String,
+    pub fields: JsonFields,
+    pub open_url: Option<String>,
+    pub timeout_secs: Option<u64>,
+    pub debounce_ms: u64,
+}
+
+impl Default for JsonEngineConfig {
+    fn default() -> Self {
+        JsonEngineConfig {
+            name: "http".to_string(),
+            prefix: "@http".to_string(),
+            description: "Search an HTTP JSON API".to_string(),
+            icon: "󰖟".to_string(),
+            base_url: String::new(),
+            path: "/".to_string(),
+            query: BTreeMap::from([("q".to_string(), "{query}".to_string())]),
+            headers: BTreeMap::new(),
+            results: String::new(),
+            fields: JsonFields::default(),
+            open_url: None,
+            timeout_secs: None,
+            debounce_ms: 300,
+        }
+    }
+}
+
+// One result, with its fields pulled out of the response.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JsonResult {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub url: Option<String>,
+    pub score: Option<f64>,
+}
+
+impl JsonEngineConfig {
+    pub fn parse_results(&self, body: &Value) -> anyhow::Result<Vec<JsonResult>> {
+        let results = body
+            .pointer(&self.results)
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow::anyhow!("no result array at `{}`", self.results))?;
+
+        let field = |result: &Value, pointer: &Option<String>| {
+            pointer
+                .as_ref()
+                .and_then(|pointer| result.pointer(pointer))
+                .and_then(text)
+        };
+
+        Ok(results
+            .iter()
+            .filter_map(|result| {
+                Some(JsonResult {
+                    title: result.pointer(&self.fields.title).and_then(text)?,
+                    subtitle: field(result, &self.fields.subtitle),
+                    url: field(result, &self.fields.url),
+                    score: self
+                        .fields
+                        .score
+                        .as_ref()
+                        .and_then(|pointer| result.pointer(pointer))
+                        .and_then(|score| match score {
+                            Value::String(s) => s.parse().ok(),
+                            score => score.as_f64(),
+                        }),
+                })
+            })
+            .collect())
+    }
+
+    pub fn open_url(&self, result: &JsonResult) -> anyhow::Result<Option<String>> {
+        let Some(template) = &self.open_url else {
+            return Ok(result.url.clone());
+        };
+
+        let fields = [
+            ("title", result.title.clone()),
+            ("subtitle", result.subtitle.clone().unwrap_or_default()),
+            ("url", result.url.clone().unwrap_or_default()),
+            (
+                "score",
+                result.score.map(|s| s.to_string()).unwrap_or_default(),
+            ),
+        ];
+        let vars: HashMap<String, String> = fields
+            .into_iter()
+            .flat_map(|(name, value)| {
+                [
+                    (name.to_string(), encode_component(&value)),
+                    (format!("{name}_raw"), value),
+                ]
+            })
+            .collect();
+        Ok(Some(strfmt(template, &vars)?))
+    }
+
+    // `path` filled in from `vars`, each encoded as a single path segment so that it can't
+    // add segments, a query or a fragment.
+    pub fn request_path(&self, vars: &HashMap<String, String>) -> anyhow::Result<String> {
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (key.clone(), encode_segment(value)))
+            .collect();
+        Ok(strfmt(&self.path, &vars)?)
+    }
+}
+
+fn encode_segment(value: &str) -> String {
+    // Pushed as is, these would step through the path rather than be part of it.
+    match value {
+        "." => return "%2E".to_string(),
+        ".." => return "%2E%2E".to_string(),
+        _ => {}
+    }
+    let mut url = Url::parse("http://localhost/").expect("static URL is valid");
+    url.path_segments_mut()
+        .expect("static URL has a path")
+        .pop()
+        .push(value);
+    url.path()[1..].to_string()
+}
+
+// `value` with everything but unreserved characters percent-encoded, so that it's taken
+// literally in a path segment, query or fragment alike.
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+// Strings are used as-is, other scalars are formatted, and missing or empty values are skipped.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if s.trim().is_empty() => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct JsonEngine {
+    config: JsonEngineConfig,
+    client: Option<surf::Client>,
+    policy: HttpPolicy,
+}
+
+impl JsonEngine {
+    pub fn new(mut config: JsonEngineConfig, http: &HttpConfig) -> Self {
+        if !config.prefix.starts_with('@') {
+            config.prefix.insert(0, '@');
+        }
+        let timeout = config
+            .timeout_secs
+            .map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+        let client = http::client(&config.base_url, timeout, &config.headers, http);
+
+        JsonEngine {
+            config,
+            client,
+            policy: HttpPolicy::new(http),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SearchEngine for JsonEngine {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn prefix(&self) -> &str {
+        &self.config.prefix
+    }
+
+    fn description(&self) -> &str {
+        &self.config.description
+    }
+
+    fn icon(&self) -> Icon {
+        Icon::Glyph(self.config.icon.clone())
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    async fn fetch(&self, request: &SearchRequest) -> anyhow::Result<Vec<ResultData>> {
+        let Some(client) = &self.client else {
+            return Ok(Vec::new());
+        };
+        if request.text.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        futures_timer::Delay::new(Duration::from_millis(self.config.debounce_ms)).await;
+
+        let vars = HashMap::from([
+            ("query".to_string(), request.text.clone()),
+            ("args".to_string(), request.args.clone().unwrap_or_default()),
+        ]);
+        let query = self
+            .config
+            .query
+            .iter()
+            .map(|(key, template)| Ok((key.clone(), strfmt(template, &vars)?)))
+            .collect::<anyhow::Result<BTreeMap<_, _>>>()?;
+
+        let request = client
+            .get(self.config.request_path(&vars)?)
+            .query(&query)
+            .map_err(|err| anyhow::anyhow!(err))?
+            .build();
+        log::info!("{} request: {}", self.config.name, request.url());
+
+        let mut response = self
+            .policy
+            .send(request, |request| async {
+                client
+                    .send(request)
+                    .await
+                    .map_err(|err| anyhow::anyhow!(err))
+            })
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!("{} returned {}", self.config.name, response.status());
+        }
+        let body: Value = response
+            .body_json()
+            .await
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        Ok(self
+            .config
+            .parse_results(&body)?
+            .into_iter()
+            .map(|result| Arc::new(result) as ResultData)
+            .collect())
+    }
+
+    fn respond(&self, data: &ResultData, rank: usize) -> Option<QueryResponse> {
+        let payload = data.clone().downcast::<JsonResult>().ok()?;
+        let result = (*payload).clone();
+        let url = self
+            .config
+            .open_url(&result)
+            .inspect_err(|e| log::error!("{}: invalid open_url: {e}", self.config.name))
+            .ok()?;
+        let priority = result
+            .score
+            .map_or(5 - rank as i64, |score| score.round() as i64);
+
+        let mut item = ResultItem::new(Icon::Glyph(self.config.icon.clone()), result.title);
+        if let Some(subtitle) = result.subtitle {
+            item = item.with_subtitle(subtitle);
+        }
+        if let Some(score) = result.score {
+            item = item.with_metadata("score", score.to_string());
+        }
+        if let Some(url) = url {
+            item = item
+                .with_metadata("url", &url)
+                .with_action("Open", ActionKind::OpenUrl(url.clone()))
+                .with_action("Copy link", ActionKind::Copy(url));
+        }
+
+        Some(QueryResponse::new(item, priority).with_payload(Payload::shared(payload)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_query_in_the_path() {
+        let config = JsonEngineConfig {
+            path: "/search/{query}/{args}".to_string(),
+            ..Default::default()
+        };
+        let vars = HashMap::from([
+            ("query".to_string(), "a/b c?d#e%".to_string()),
+            ("args".to_string(), "..".to_string()),
+        ]);
+        assert_eq!(
+            config.request_path(&vars).unwrap(),
+            "/search/a%2Fb%20c%3Fd%23e%25/%2E%2E"
+        );
+    }
+
+    #[test]
+    fn encodes_fields_in_the_open_url() {
+        let config = JsonEngineConfig {
+            open_url: Some("https://example.org/?q={title}&from={url}#{title_raw}".to_string()),
+            ..Default::default()
+        };
+        let result = JsonResult {
+            title: "C++ & Rust #1".to_string(),
+            subtitle: None,
+            url: Some("https://a.example/x?y=1".to_string()),
+            score: None,
+        };
+        assert_eq!(
+            config.open_url(&result).unwrap().as_deref(),
+            Some(
+                "https://example.org/?q=C%2B%2B%20%26%20Rust%20%231\
+                 &from=https%3A%2F%2Fa.example%2Fx%3Fy%3D1#C++ & Rust #1"
+            )
+        );
+    }
+
+    #[test]
+    fn maps_results_through_json_pointers() {
+        let config = JsonEngineConfig {
+            results: "/data/hits".to_string(),
+            fields: JsonFields {
+                title: "/name".to_string(),
+                subtitle: Some("/meta/summary".to_string()),
+                url: Some("/id".to_string()),
+                score: Some("/meta/score".to_string()),
+            },
+            open_url: Some("https://example.org/doc/{url}".to_string()),
+            ..Default::default()
+        };
+        let body = serde_json::json!({"data": {"hits": [
+            {"name": "First", "id": 7, "meta": {"summary": "one", "score": 9.6}},
+            {"name": "", "id": 8},
+            {"name": "Third", "id": "c", "meta": {"score": "2"}},
+        ]}});
+
+        let results = config.parse_results(&body).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                JsonResult {
+                    title: "First".to_string(),
+                    subtitle: Some("one".to_string()),
+                    url: Some("7".to_string()),
+                    score: Some(9.6),
+                },
+                JsonResult {
+                    title: "Third".to_string(),
+                    subtitle: None,
+                    url: Some("c".to_string()),
+                    score: Some(2.),
+                },
+            ]
+        );
+        assert_eq!(
+            config.open_url(&results[0]).unwrap().as_deref(),
+            Some("https://example.org/doc/7")
+        );
+        assert!(config.parse_results(&serde_json::json!({})).is_err());
+    }
+}
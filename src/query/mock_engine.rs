@@ -1,48 +1,44 @@
-use crate::query::SearchEngine;
-use crate::response::QueryResponse;
-use egui::Ui;
+use crate::query::{SearchEngine, SearchRequest};
+use crate::response::{ActionKind, Icon, QueryResponse, ResultItem};
 use flume::Sender;
 use std::time::Instant;
 
+const ICON: &str = "󰤑";
+
 #[derive(Debug, Default)]
 pub struct MockEngine;
 
 #[async_trait::async_trait]
 impl SearchEngine for MockEngine {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "mock_engine"
     }
 
-    fn prefix(&self) -> &'static str {
+    fn prefix(&self) -> &str {
         "@mk"
     }
 
-    fn icon(&self) -> Box<dyn Fn(&mut Ui) -> egui::Response + Send> {
-        Box::new(|ui| ui.monospace("󰤑"))
+    fn description(&self) -> &str {
+        "Echo the query back"
+    }
+
+    fn icon(&self) -> Icon {
+        Icon::Glyph(ICON.into())
     }
 
-    async fn search(&self, query: &str, channel: Sender<QueryResponse>) -> anyhow::Result<()> {
+    async fn search(
+        &self,
+        request: &SearchRequest,
+        channel: Sender<QueryResponse>,
+    ) -> anyhow::Result<()> {
+        let query = request.text.as_str();
         let start = Instant::now();
         log::info!("Received search request: {query}");
 
-        let loc_query = query.to_string();
-        let icon = self.icon();
+        let item = ResultItem::new(Icon::Glyph(ICON.to_string()), query)
+            .with_action("Copy", ActionKind::Copy(query.to_string()));
         let res = channel
-            .send_async(
-                QueryResponse::new(
-                    Box::new(move |ui: &mut egui::Ui| {
-                        icon(ui);
-
-                        ui.add(
-                            egui::Label::new(egui::RichText::new(loc_query.to_string()))
-                                .wrap_mode(egui::TextWrapMode::Wrap),
-                        )
-                    }),
-                    Box::new(|_: &mut egui::Ui| {}),
-                    0,
-                )
-                .with_duration(start.elapsed()),
-            )
+            .send_async(QueryResponse::new(item, 0).with_duration(start.elapsed()))
             .await;
 
         if let Err(err) = res {
@@ -1,62 +1,225 @@
+//! Search engines and the [`QueryEngine`] that runs a query against all of them.
+
+pub mod cache;
 mod content_search;
 mod file_search;
+pub mod http;
+#[cfg(feature = "gui")]
+pub mod images;
+mod json_engine;
 mod mock_engine;
+mod oauth;
+pub mod parser;
+#[cfg(test)]
+mod test_server;
+pub mod waker;
 mod wikipedia;
 
-use crate::query::content_search::Rga;
-use crate::query::file_search::Fzf;
-use crate::query::mock_engine::MockEngine;
-use crate::query::wikipedia::WikipediaEngine;
-use crate::response::QueryResponse;
+pub use content_search::Rga;
+pub use file_search::Fzf;
+pub use json_engine::{JsonEngine, JsonEngineConfig, JsonFields};
+pub use mock_engine::MockEngine;
+pub use wikipedia::{SearchMode, WikipediaConfig, WikipediaEngine};
+
+use crate::query::cache::{CacheConfig, CacheKey, Lookup, ResultCache, ResultData};
+use crate::query::http::{HttpConfig, HttpError};
+#[cfg(feature = "gui")]
+use crate::query::parser::Filter;
+use crate::query::parser::Query;
+use crate::query::waker::Waker;
+use crate::response::{ActionKind, Icon, Payload, QueryResponse, ResultItem};
+#[cfg(feature = "gui")]
 use egui::Ui;
 use flume::Receiver;
+use futures::FutureExt;
 use futures::executor::ThreadPool;
-use futures::future::{RemoteHandle, join_all};
+use futures::future::{Either, RemoteHandle, join_all, select};
 use futures::task::SpawnExt;
+use futures_timer::Delay;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::collections::HashMap;
-use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 pub struct QueryConfig {
-    pool_size: usize,
+    pub pool_size: usize,
+    // How many of the best results a query keeps; the rest are dropped as they arrive.
+    pub result_limit: usize,
+    pub wikipedia: WikipediaConfig,
+    pub json_engines: Vec<JsonEngineConfig>,
+    pub cache: CacheConfig,
+    pub http: HttpConfig,
+    pub timeouts: TimeoutConfig,
 }
 
 impl Default for QueryConfig {
     fn default() -> Self {
-        QueryConfig { pool_size: 0 }
+        QueryConfig {
+            pool_size: 0,
+            result_limit: 5000,
+            wikipedia: WikipediaConfig::default(),
+            json_engines: Vec::new(),
+            cache: CacheConfig::default(),
+            http: HttpConfig::default(),
+            timeouts: TimeoutConfig::default(),
+        }
+    }
+}
+
+// How long an engine may run before it's cancelled, unless overridden by name in `engines`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub default_ms: u64,
+    pub engines: HashMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            default_ms: 30_000,
+            engines: HashMap::new(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn for_engine(&self, engine: &str) -> Duration {
+        Duration::from_millis(*self.engines.get(engine).unwrap_or(&self.default_ms))
     }
 }
 
-pub struct EngineCollection(HashMap<&'static str, Vec<Box<dyn SearchEngine + Sync + Send>>>);
+/// A shared handle to a search engine.
+pub type Engine = Arc<dyn SearchEngine + Sync + Send>;
+
+/// The engines a query can run against, grouped by prefix.
+pub struct EngineCollection(HashMap<String, Vec<Engine>>);
 
-lazy_static::lazy_static! {
-    pub static ref ENGINES: RwLock<EngineCollection> = RwLock::new({
-        let engines: Vec<Box<dyn SearchEngine + Sync + Send>> = vec![
-            Box::new(MockEngine),
-            Box::new(WikipediaEngine::new()),
-            Box::new(Fzf::new()),
-            Box::new(Rga::new()),
+impl EngineCollection {
+    /// The built-in engines, configured by `config`.
+    pub fn new(config: &QueryConfig) -> Self {
+        let mut engines: Vec<Engine> = vec![
+            Arc::new(MockEngine),
+            Arc::new(WikipediaEngine::new(config.wikipedia.clone(), &config.http)),
+            Arc::new(Fzf::new()),
+            Arc::new(Rga::new()),
         ];
+        engines.extend(
+            config
+                .json_engines
+                .iter()
+                .map(|engine| Arc::new(JsonEngine::new(engine.clone(), &config.http)) as Engine),
+        );
 
-        let mut map = HashMap::new();
+        let mut collection = EngineCollection(HashMap::new());
         for engine in engines.into_iter() {
-            map.entry(engine.prefix()).or_insert(Vec::new()).push(engine);
+            collection.register(engine);
         }
+        collection
+    }
+
+    /// Adds `engine` under its prefix, next to any engines already sharing it.
+    pub fn register(&mut self, engine: Engine) {
+        self.0
+            .entry(engine.prefix().to_string())
+            .or_default()
+            .push(engine);
+    }
+
+    /// The engines `query` runs against.
+    pub fn selected(&self, query: &Query) -> Vec<Engine> {
+        self.0
+            .iter()
+            .filter(|(prefix, _)| query.selects(prefix))
+            .flat_map(|(_, engines)| engines)
+            .cloned()
+            .collect()
+    }
+
+    /// Every engine, sorted by prefix and name.
+    pub fn all(&self) -> Vec<Engine> {
+        let mut engines: Vec<_> = self.0.values().flatten().cloned().collect();
+        engines.sort_by(|a, b| (a.prefix(), a.name()).cmp(&(b.prefix(), b.name())));
+        engines
+    }
+}
+
+/// What an engine is asked to search for.
+#[derive(Debug, Clone, Default)]
+pub struct SearchRequest {
+    /// The query text, without filters or modifiers and with phrases unquoted.
+    pub text: String,
+    /// The arguments given to this engine's filter, e.g. `~/src` in `@rg:~/src`.
+    pub args: Option<String>,
+    /// The flags given to this engine's filter, e.g. `text` in `@wi+text`.
+    pub flags: Vec<String>,
+    /// The full parsed query.
+    pub query: Query,
+}
+
+impl SearchRequest {
+    /// The request for the engine registered under `prefix`.
+    pub fn new(query: &Query, prefix: &str) -> Self {
+        let filter = query.filter_for(prefix);
+        SearchRequest {
+            text: query.text(),
+            args: filter.and_then(|f| f.args.clone()),
+            flags: filter.map(|f| f.flags.clone()).unwrap_or_default(),
+            query: query.clone(),
+        }
+    }
+
+    /// The value of the first `key:value` modifier in the query.
+    pub fn modifier(&self, key: &str) -> Option<&str> {
+        self.query
+            .modifiers()
+            .find(|m| m.key == key)
+            .map(|m| m.value.as_str())
+    }
+}
 
-        EngineCollection(map)
+// A non-result row telling the user why an engine has nothing to show.
+fn status_row(engine: &Engine, message: String) -> QueryResponse {
+    let response = QueryResponse::new(ResultItem::new(engine.icon(), message), i64::MIN);
+    #[cfg(feature = "gui")]
+    let response = response.with_widget(|ui: &mut Ui, item: &ResultItem| {
+        item.icon.ui(ui);
+        ui.weak(&item.title)
     });
+    response
 }
 
+fn panic_message(panic: &Box<dyn Any + Send>) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("panicked")
+}
+
+/// The query in flight; dropping it cancels the query.
 pub struct QueryState {
     pub _handle: RemoteHandle<()>,
     pub rcv: Receiver<QueryResponse>,
 }
 
+/// Runs queries against every selected engine on a thread pool, streaming their
+/// responses back through a channel.
 pub struct QueryEngine {
     thread_pool: ThreadPool,
     query_state: RwLock<Option<QueryState>>,
+    engines: EngineCollection,
+    // Engines added through `register`, kept across rebuilds from the config.
+    registered: Vec<Engine>,
+    cache: Arc<ResultCache>,
+    waker: Option<Arc<Waker>>,
+    timeouts: TimeoutConfig,
 }
 
 impl std::fmt::Debug for QueryEngine {
@@ -69,6 +232,7 @@ impl std::fmt::Debug for QueryEngine {
 }
 
 impl QueryEngine {
+    /// Runs queries against the built-in engines, configured by `config`.
     pub fn new(config: &QueryConfig) -> Self {
         QueryEngine {
             thread_pool: ThreadPool::builder()
@@ -80,59 +244,232 @@ impl QueryEngine {
                 .create()
                 .expect("Failed to create thread pool"),
             query_state: RwLock::new(None),
+            engines: EngineCollection::new(config),
+            registered: Vec::new(),
+            cache: Arc::new(ResultCache::new(config.cache.clone())),
+            waker: None,
+            timeouts: config.timeouts.clone(),
         }
     }
 
-    pub fn icon(&self, filter: &Option<String>, ui: &mut Ui) {
-        if let Some(filter) = filter {
-            if ENGINES.read().0.contains_key(filter.as_str())
-                && let Some(engine) = ENGINES.read().0[filter.as_str()].first()
-            {
-                engine.icon()(ui);
-            } else {
-                ui.monospace("");
-            }
-        } else {
+    /// Wakes `waker` whenever responses arrive, instead of leaving the reader to poll.
+    pub fn with_waker(mut self, waker: Arc<Waker>) -> Self {
+        self.waker = Some(waker);
+
+        self
+    }
+
+    /// A query engine for `config` with the same registered engines and waker as this one.
+    pub fn rebuild(&self, config: &QueryConfig) -> Self {
+        let mut engine = QueryEngine::new(config);
+        engine.waker = self.waker.clone();
+        for registered in &self.registered {
+            engine.register(registered.clone());
+        }
+
+        engine
+    }
+
+    /// Adds `engine` next to the built-in engines.
+    pub fn register(&mut self, engine: Engine) {
+        self.registered.push(engine.clone());
+        self.engines.register(engine);
+    }
+
+    /// Every engine, sorted by prefix and name.
+    pub fn engines(&self) -> Vec<Engine> {
+        self.engines.all()
+    }
+
+    /// Shows the icon of the engine `filter` selects, or a search icon without one.
+    #[cfg(feature = "gui")]
+    pub fn icon(&self, filter: Option<&Filter>, ui: &mut Ui) {
+        let Some(filter) = filter else {
             ui.monospace("󰍉");
+            return;
+        };
+
+        if filter.exclude {
+            ui.weak("-");
+        }
+        if let Some(engine) = self
+            .engines
+            .0
+            .get(filter.prefix.as_str())
+            .and_then(|engines| engines.first())
+        {
+            engine.icon().ui(ui);
+        } else {
+            ui.monospace("");
+        }
+        for flag in &filter.flags {
+            ui.small(format!("+{flag}"));
+        }
+        if let Some(args) = &filter.args {
+            ui.small(args);
         }
     }
 
-    pub async fn query_async(
-        query: String,
-        filter: Option<String>,
+    // Runs `query` against `engines`, concurrently, sending their responses to `snd` as they
+    // arrive and waking `waker` for each. Each engine runs as a task of its own, so one that
+    // blocks, hangs or panics only takes itself down, and is reported as a status row.
+    async fn search_all(
+        engines: Vec<Engine>,
+        query: Query,
         snd: flume::Sender<QueryResponse>,
+        cache: Arc<ResultCache>,
+        pool: ThreadPool,
+        waker: Option<Arc<Waker>>,
+        timeouts: TimeoutConfig,
     ) {
-        let _ = join_all(
-            ENGINES
-                .deref()
-                .read()
-                .0
-                .iter()
-                .filter_map(|(&prefix, engines)| match &filter {
-                    None => Some(engines),
-                    Some(p) if p == prefix => Some(engines),
-                    _ => None,
-                })
-                .flatten()
-                .map(|engine| async {
-                    if let Err(e) = engine.search(&query, snd.clone()).await {
-                        log::error!("Query Error: {e}");
+        let _ = join_all(engines.iter().map(|engine| async {
+            // Engines send straight to a channel, so their responses are relayed to tag them
+            // with the engine and their key, and wake the reader.
+            let (relay, rcv) = flume::bounded::<QueryResponse>(1024);
+            let failed = AtomicBool::new(false);
+            let send = |response: QueryResponse| async {
+                let mut response = response.with_key();
+                if response.engines.is_empty() {
+                    response.engines.push(engine.name().to_string());
+                    response.source = Some(engine.clone());
+                }
+                let sent = snd.send_async(response).await.is_ok();
+                if let Some(waker) = &waker {
+                    waker.wake();
+                }
+                sent
+            };
+            let forward = async {
+                while let Ok(response) = rcv.recv_async().await {
+                    // Whatever a cancelled engine still sends is dropped along with it.
+                    if failed.load(Ordering::Acquire) || !send(response).await {
+                        break;
                     }
-                }),
-        )
+                }
+            };
+
+            let search = QueryEngine::search_engine(
+                engine.clone(),
+                SearchRequest::new(&query, engine.prefix()),
+                relay.clone(),
+                cache.clone(),
+                pool.clone(),
+            );
+            let timeout = timeouts.for_engine(engine.name());
+            let run = async {
+                // Owned here so that the relay closes once the engine is done.
+                let _relay = relay;
+                let handle = match pool.spawn_with_handle(AssertUnwindSafe(search).catch_unwind()) {
+                    Ok(handle) => handle,
+                    Err(e) => {
+                        log::error!("Failed to start {}: {e}", engine.name());
+                        return;
+                    }
+                };
+                // Dropping the handle on timeout cancels the engine.
+                let failure = match select(handle, Delay::new(timeout)).await {
+                    Either::Left((Ok(()), _)) => return,
+                    Either::Left((Err(panic), _)) => {
+                        format!("{} crashed: {}", engine.name(), panic_message(&panic))
+                    }
+                    Either::Right(_) => format!(
+                        "{} timed out after {:.1} s",
+                        engine.name(),
+                        timeout.as_secs_f64()
+                    ),
+                };
+                log::error!("{failure}");
+                failed.store(true, Ordering::Release);
+                send(status_row(engine, failure)).await;
+            };
+
+            futures::join!(run, forward);
+        }))
         .await;
     }
 
-    pub fn query(&mut self, query: &str, filter: &Option<String>) {
+    async fn search_engine(
+        engine: Engine,
+        request: SearchRequest,
+        snd: flume::Sender<QueryResponse>,
+        cache: Arc<ResultCache>,
+        pool: ThreadPool,
+    ) {
+        let result = if engine.cacheable() && cache.enabled_for(engine.name()) {
+            QueryEngine::search_cached(&engine, request, snd.clone(), &cache, &pool).await
+        } else {
+            engine.search(&request, snd.clone()).await
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if e.downcast_ref::<HttpError>().is_some() => {
+                log::warn!("{} is offline: {e}", engine.name());
+                let _ = snd
+                    .send_async(status_row(&engine, format!("{} is {e}", engine.name())))
+                    .await;
+            }
+            Err(e) => log::error!("Query Error ({}): {e}", engine.name()),
+        }
+    }
+
+    // Serves results from the cache when possible, refetching stale ones in the background.
+    async fn search_cached(
+        engine: &Engine,
+        request: SearchRequest,
+        snd: flume::Sender<QueryResponse>,
+        cache: &Arc<ResultCache>,
+        pool: &ThreadPool,
+    ) -> anyhow::Result<()> {
+        let key = CacheKey::new(engine.name(), &request);
+        let results = match cache.get(&key) {
+            Lookup::Fresh(results) => results,
+            Lookup::Stale(results) => {
+                let (engine, cache) = (engine.clone(), cache.clone());
+                pool.spawn_ok(async move {
+                    match engine.fetch(&request).await {
+                        Ok(results) => cache.insert(key, Arc::new(results)),
+                        Err(e) => {
+                            log::warn!("Revalidation failed ({}): {e}", engine.name());
+                            cache.revalidation_failed(&key);
+                        }
+                    }
+                });
+                results
+            }
+            Lookup::Miss => {
+                let results = Arc::new(engine.fetch(&request).await?);
+                cache.insert(key, results.clone());
+                results
+            }
+        };
+
+        for (rank, data) in results.iter().enumerate() {
+            if let Some(response) = engine.respond(data, rank)
+                && snd.send_async(response).await.is_err()
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts running `query`, cancelling the previous one. Its responses are read from
+    /// [`QueryEngine::responses`].
+    pub fn query(&mut self, query: &str) {
         log::info!("Query: {}", query);
         let (snd, rcv) = flume::bounded(1024);
+        let query = Query::parse(query);
 
         if let Ok(handle) = self
             .thread_pool
-            .spawn_with_handle(QueryEngine::query_async(
-                query.to_string(),
-                filter.clone(),
+            .spawn_with_handle(QueryEngine::search_all(
+                self.engines.selected(&query),
+                query,
                 snd,
+                self.cache.clone(),
+                self.thread_pool.clone(),
+                self.waker.clone(),
+                self.timeouts.clone(),
             ))
             .inspect_err(|e| log::error!("{e}"))
         {
@@ -143,10 +480,12 @@ impl QueryEngine {
         }
     }
 
+    /// Cancels the query in flight.
     pub fn clear_query(&mut self) {
         drop(self.query_state.write().take());
     }
 
+    /// Whether `rcv` belongs to the current query rather than an earlier one.
     pub fn match_receiver(&self, rcv: &Receiver<QueryResponse>) -> bool {
         self.query_state
             .read()
@@ -155,6 +494,7 @@ impl QueryEngine {
             .unwrap_or(false)
     }
 
+    /// The receiving end of the current query's responses.
     pub fn responses(&self) -> Option<Receiver<QueryResponse>> {
         if let Some(QueryState { rcv, .. }) = &*self.query_state.read() {
             Some(rcv.clone())
@@ -164,15 +504,244 @@ impl QueryEngine {
     }
 }
 
+/// A source of results.
+///
+/// Engines either stream responses from [`SearchEngine::search`], or, when their results
+/// are plain data, implement [`SearchEngine::fetch`] and [`SearchEngine::respond`] instead
+/// and let the default `search` tie them together. The latter can be cached by
+/// [`QueryEngine`] when [`SearchEngine::cacheable`] says so.
+///
+/// ```no_run
+/// # use amoeba::query::cache::ResultData;
+/// # use amoeba::query::{QueryConfig, QueryEngine, SearchEngine, SearchRequest};
+/// # use amoeba::response::{Icon, QueryResponse, ResultItem};
+/// # use std::sync::Arc;
+/// struct Echo;
+///
+/// #[async_trait::async_trait]
+/// impl SearchEngine for Echo {
+///     fn name(&self) -> &str { "echo" }
+///     fn prefix(&self) -> &str { "@echo" }
+///     fn description(&self) -> &str { "Repeat the query" }
+///     fn icon(&self) -> Icon {
+///         Icon::Glyph("E".into())
+///     }
+///
+///     async fn fetch(&self, request: &SearchRequest) -> anyhow::Result<Vec<ResultData>> {
+///         Ok(vec![Arc::new(request.text.clone())])
+///     }
+///
+///     fn respond(&self, data: &ResultData, _rank: usize) -> Option<QueryResponse> {
+///         let text = data.downcast_ref::<String>()?.clone();
+///         let item = ResultItem::new(Icon::Glyph("".into()), text);
+///         Some(QueryResponse::new(item, 0))
+///     }
+/// }
+///
+/// let mut engine = QueryEngine::new(&QueryConfig::default());
+/// engine.register(Arc::new(Echo));
+/// ```
 #[async_trait::async_trait]
 pub trait SearchEngine {
-    fn name(&self) -> &'static str;
-    fn prefix(&self) -> &'static str;
-    fn icon(&self) -> Box<dyn Fn(&mut Ui) -> egui::Response + Send>;
+    /// A unique name, also used to configure the engine's cache policy.
+    fn name(&self) -> &str;
+    /// The `@prefix` that selects this engine in a query.
+    fn prefix(&self) -> &str;
+    /// A one-line description for the engine picker.
+    fn description(&self) -> &str;
+    /// Shown on the engine's status rows and in the engine picker.
+    fn icon(&self) -> Icon;
+
+    /// Whether `QueryEngine` may cache the results of [`SearchEngine::fetch`].
+    fn cacheable(&self) -> bool {
+        false
+    }
 
+    /// Sends responses for `request` to `channel` until done or the channel is closed,
+    /// which happens once the query is superseded.
     async fn search(
         &self,
-        query: &str,
+        request: &SearchRequest,
         channel: flume::Sender<QueryResponse>,
-    ) -> anyhow::Result<()>;
+    ) -> anyhow::Result<()> {
+        for (rank, data) in self.fetch(request).await?.iter().enumerate() {
+            if let Some(response) = self.respond(data, rank)
+                && channel.send_async(response).await.is_err()
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The results for `request`, best first, as plain data.
+    async fn fetch(&self, _request: &SearchRequest) -> anyhow::Result<Vec<ResultData>> {
+        anyhow::bail!("{} can't fetch results as data", self.name())
+    }
+
+    /// Turns the `rank`th result of `fetch` into a response. Called again on every cache hit.
+    fn respond(&self, _data: &ResultData, _rank: usize) -> Option<QueryResponse> {
+        None
+    }
+
+    /// Runs an [`ActionKind::Engine`] action named `action` on one of this engine's results,
+    /// given the payload it was sent with. Returns a plain action for the launcher to carry
+    /// out when the engine can't finish the job itself, such as copying to the clipboard.
+    fn run_action(
+        &self,
+        action: &str,
+        _payload: Option<&Payload>,
+    ) -> anyhow::Result<Option<ActionKind>> {
+        anyhow::bail!("{} has no action {action}", self.name())
+    }
+
+    /// Called when `query` is recorded in the history because one of this engine's results
+    /// ran, with that result's payload.
+    fn record(&self, _query: &str, _payload: Option<&Payload>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    enum Behavior {
+        Answer,
+        Panic,
+        Block(Duration),
+        Hang,
+    }
+
+    struct Misbehaving(&'static str, Behavior);
+
+    #[async_trait::async_trait]
+    impl SearchEngine for Misbehaving {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn prefix(&self) -> &str {
+            "@test"
+        }
+
+        fn description(&self) -> &str {
+            "Misbehave on purpose"
+        }
+
+        fn icon(&self) -> Icon {
+            Icon::Glyph("T".into())
+        }
+
+        async fn fetch(&self, request: &SearchRequest) -> anyhow::Result<Vec<ResultData>> {
+            match self.1 {
+                Behavior::Answer => {}
+                Behavior::Panic => panic!("bad engine"),
+                Behavior::Block(duration) => std::thread::sleep(duration),
+                Behavior::Hang => futures::future::pending::<()>().await,
+            }
+            Ok(vec![Arc::new(request.text.clone())])
+        }
+
+        fn respond(&self, data: &ResultData, _rank: usize) -> Option<QueryResponse> {
+            let text = data.downcast_ref::<String>()?;
+            Some(QueryResponse::new(
+                ResultItem::new(Icon::Glyph("T".into()), format!("{}: {text}", self.0)),
+                0,
+            ))
+        }
+    }
+
+    // Runs `engines` on "x", returning the titles received and when.
+    fn run(engines: Vec<(&'static str, Behavior)>, timeout_ms: u64) -> Vec<(String, Duration)> {
+        let pool = ThreadPool::builder().pool_size(4).create().unwrap();
+        let engines = engines
+            .into_iter()
+            .map(|(name, behavior)| Arc::new(Misbehaving(name, behavior)) as Engine)
+            .collect();
+        let (snd, rcv) = flume::unbounded();
+        let timeouts = TimeoutConfig {
+            default_ms: timeout_ms,
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let query = QueryEngine::search_all(
+            engines,
+            Query::parse("x"),
+            snd,
+            Arc::new(ResultCache::default()),
+            pool.clone(),
+            None,
+            timeouts,
+        );
+        let collect = async {
+            let mut received = Vec::new();
+            while let Ok(response) = rcv.recv_async().await {
+                received.push((response.item.title, start.elapsed()));
+            }
+            received
+        };
+        futures::executor::block_on(futures::future::join(query, collect)).1
+    }
+
+    fn titles(received: &[(String, Duration)]) -> Vec<&str> {
+        let mut titles: Vec<_> = received.iter().map(|(title, _)| title.as_str()).collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn registered_engines_belong_to_their_query_engine() {
+        let names = |engine: &QueryEngine| -> Vec<String> {
+            engine
+                .engines()
+                .iter()
+                .map(|engine| engine.name().to_string())
+                .collect()
+        };
+        let mut first = QueryEngine::new(&QueryConfig::default());
+        let second = QueryEngine::new(&QueryConfig::default());
+        first.register(Arc::new(Misbehaving("extra", Behavior::Answer)));
+
+        assert!(names(&first).contains(&"extra".to_string()));
+        assert!(!names(&second).contains(&"extra".to_string()));
+        // Rebuilding from a new config keeps the engine.
+        let rebuilt = first.rebuild(&QueryConfig::default());
+        assert_eq!(names(&rebuilt), names(&first));
+    }
+
+    #[test]
+    fn reports_panicking_engines() {
+        // Printing the panic can take a while, so it gets plenty of time.
+        let received = run(
+            vec![("good", Behavior::Answer), ("bad", Behavior::Panic)],
+            10_000,
+        );
+        assert_eq!(titles(&received), ["bad crashed: bad engine", "good: x"]);
+    }
+
+    #[test]
+    fn times_out_hanging_engines() {
+        let received = run(
+            vec![("good", Behavior::Answer), ("slow", Behavior::Hang)],
+            200,
+        );
+        assert_eq!(titles(&received), ["good: x", "slow timed out after 0.2 s"]);
+    }
+
+    #[test]
+    fn blocking_engines_dont_delay_others() {
+        let received = run(
+            vec![
+                ("slow", Behavior::Block(Duration::from_secs(1))),
+                ("good", Behavior::Answer),
+            ],
+            200,
+        );
+        assert_eq!(titles(&received), ["good: x", "slow timed out after 0.2 s"]);
+
+        let arrived = |title: &str| received.iter().find(|(t, _)| t == title).unwrap().1;
+        assert!(arrived("good: x") < Duration::from_millis(500));
+        assert!(arrived("slow timed out after 0.2 s") < Duration::from_millis(900));
+    }
 }
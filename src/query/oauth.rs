@@ -0,0 +1,269 @@
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use surf::{Client, Request, Response, StatusCode};
+
+// Tokens are refreshed this long before they expire, so a request never races the expiry.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    // A long-lived personal token; it can't be refreshed.
+    Static(String),
+    ClientCredentials {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct TokenRequest<'a> {
+    grant_type: &'static str,
+    client_id: &'a str,
+    client_secret: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    token: String,
+    expires_at: Option<Instant>,
+}
+
+impl CachedToken {
+    fn is_fresh(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| Instant::now() + EXPIRY_MARGIN < expires_at)
+    }
+}
+
+// Hands out OAuth 2 bearer tokens, exchanging client credentials for a new one when the
+// cached token has expired or been rejected.
+#[derive(Debug)]
+pub struct TokenSource {
+    credentials: Credentials,
+    cached: Mutex<Option<CachedToken>>,
+}
+
+impl TokenSource {
+    pub fn new(credentials: Credentials) -> Self {
+        TokenSource {
+            credentials,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub async fn token(&self, client: &Client) -> anyhow::Result<String> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref().filter(|token| token.is_fresh()) {
+            return Ok(token.token.clone());
+        }
+
+        let token = self.fetch(client).await?;
+        let value = token.token.clone();
+        *cached = Some(token);
+        Ok(value)
+    }
+
+    pub async fn invalidate(&self) {
+        self.cached.lock().await.take();
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<CachedToken> {
+        let (token_url, client_id, client_secret) = match &self.credentials {
+            Credentials::Static(token) => {
+                return Ok(CachedToken {
+                    token: token.clone(),
+                    expires_at: None,
+                });
+            }
+            Credentials::ClientCredentials {
+                token_url,
+                client_id,
+                client_secret,
+            } => (token_url, client_id, client_secret),
+        };
+
+        log::info!("Requesting OAuth token from {token_url}");
+        let mut request = client.post(token_url).build();
+        request
+            .body_form(&TokenRequest {
+                grant_type: "client_credentials",
+                client_id,
+                client_secret,
+            })
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        let mut response = client
+            .send(request)
+            .await
+            .map_err(|err| anyhow::anyhow!(err))?;
+        if !response.status().is_success() {
+            let body = response.body_string().await.unwrap_or_default();
+            anyhow::bail!("token request failed with {}: {body}", response.status());
+        }
+
+        let token: TokenResponse = response
+            .body_json()
+            .await
+            .map_err(|err| anyhow::anyhow!(err))?;
+        Ok(CachedToken {
+            token: token.access_token,
+            expires_at: token
+                .expires_in
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        })
+    }
+
+    // Sends `request` with a bearer token, retrying once with a new token if it's rejected.
+    pub async fn send(&self, client: &Client, request: Request) -> anyhow::Result<Response> {
+        let response = self.send_once(client, request.clone()).await?;
+        if response.status() != StatusCode::Unauthorized
+            || matches!(self.credentials, Credentials::Static(_))
+        {
+            return Ok(response);
+        }
+
+        log::info!("OAuth token rejected, refreshing");
+        self.invalidate().await;
+        self.send_once(client, request).await
+    }
+
+    async fn send_once(&self, client: &Client, mut request: Request) -> anyhow::Result<Response> {
+        let token = self.token(client).await?;
+        request.insert_header("Authorization", format!("Bearer {token}"));
+        client
+            .send(request)
+            .await
+            .map_err(|err| anyhow::anyhow!(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::http::USER_AGENT;
+    use crate::query::test_server::{self, Reply};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Answers every request on a local port with the JSON `handler(request) -> (status, body)`.
+    fn serve(handler: impl Fn(&str) -> (&'static str, String) + Send + 'static) -> String {
+        test_server::serve(move |_, request| {
+            let (status, body) = handler(request);
+            Reply::new(status)
+                .with_header("Content-Type", "application/json")
+                .with_body(body)
+        })
+        .0
+    }
+
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then_some(value.trim())
+        })
+    }
+
+    // A token endpoint issuing `token-1`, `token-2`, ... and counting its calls.
+    fn token_endpoint(expires_in: u64) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let url = serve(move |request| {
+            if !request.starts_with("POST")
+                || !request.contains("grant_type=client_credentials")
+                || !request.contains("client_id=id")
+                || !request.contains("client_secret=secret")
+            {
+                return ("400 Bad Request", "{}".to_string());
+            }
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            (
+                "200 OK",
+                format!(
+                    r#"{{"token_type":"Bearer","access_token":"token-{n}","expires_in":{expires_in}}}"#
+                ),
+            )
+        });
+        (format!("{url}/oauth2/access_token"), calls)
+    }
+
+    fn source(token_url: String) -> TokenSource {
+        TokenSource::new(Credentials::ClientCredentials {
+            token_url,
+            client_id: "id".to_string(),
+            client_secret: "secret".to_string(),
+        })
+    }
+
+    #[test]
+    fn caches_token_until_it_expires() {
+        futures::executor::block_on(async {
+            let client = Client::new();
+            let (url, calls) = token_endpoint(3600);
+            let source = source(url);
+
+            assert_eq!(source.token(&client).await.unwrap(), "token-1");
+            assert_eq!(source.token(&client).await.unwrap(), "token-1");
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+            source.invalidate().await;
+            assert_eq!(source.token(&client).await.unwrap(), "token-2");
+
+            // Tokens expiring within the margin are never reused.
+            let (url, calls) = token_endpoint(1);
+            let source = self::source(url);
+            source.token(&client).await.unwrap();
+            source.token(&client).await.unwrap();
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn refreshes_token_on_unauthorized() {
+        futures::executor::block_on(async {
+            let client = Client::new();
+            let (token_url, calls) = token_endpoint(3600);
+            // Only the second token issued is accepted, as if the first was revoked.
+            let api = serve(|request| {
+                let user_agent = header(request, "user-agent");
+                let authorization = header(request, "authorization");
+                match (user_agent == Some(USER_AGENT), authorization) {
+                    (false, _) => ("400 Bad Request", "no user agent".to_string()),
+                    (true, Some("Bearer token-2")) => ("200 OK", "{}".to_string()),
+                    (true, _) => ("401 Unauthorized", "{}".to_string()),
+                }
+            });
+
+            let source = source(token_url);
+            let request = client
+                .get(format!("{api}/search"))
+                .header("User-Agent", USER_AGENT)
+                .build();
+            let response = source.send(&client, request).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::Ok);
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn reports_token_endpoint_errors() {
+        futures::executor::block_on(async {
+            let url = serve(|_| {
+                (
+                    "401 Unauthorized",
+                    r#"{"error":"invalid_client"}"#.to_string(),
+                )
+            });
+            let err = source(url).token(&Client::new()).await.unwrap_err();
+            assert!(err.to_string().contains("invalid_client"), "{err}");
+        });
+    }
+}
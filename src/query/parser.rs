@@ -0,0 +1,397 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+// All spans are char (not byte) ranges into the parsed input, so they can be
+// used directly with egui's `CCursor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Filter(Filter),
+    Term(Term),
+    Modifier(Modifier),
+}
+
+// `@prefix`, `@prefix+flag:args` or `-@prefix` to exclude an engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub prefix: String,
+    pub flags: Vec<String>,
+    pub args: Option<String>,
+    pub exclude: bool,
+    pub span: Range<usize>,
+}
+
+// A bare word or a `"quoted phrase"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub text: String,
+    pub quoted: bool,
+    pub span: Range<usize>,
+}
+
+// The keys engines understand as `key:value` modifiers. Anything else with a colon, like
+// `10:30`, is searched for as is.
+pub const MODIFIER_KEYS: &[&str] = &["ext", "limit"];
+
+// `key:value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modifier {
+    pub key: String,
+    pub value: String,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub nodes: Vec<Node>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Self {
+        Parser::new(input).parse()
+    }
+
+    pub fn filters(&self) -> impl Iterator<Item = &Filter> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Filter(filter) => Some(filter),
+            _ => None,
+        })
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = &Term> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Term(term) => Some(term),
+            _ => None,
+        })
+    }
+
+    pub fn modifiers(&self) -> impl Iterator<Item = &Modifier> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Modifier(modifier) => Some(modifier),
+            _ => None,
+        })
+    }
+
+    // The search text with filters and modifiers stripped and phrases unquoted.
+    pub fn text(&self) -> String {
+        self.terms()
+            .map(|term| term.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Whether an engine registered under `prefix` should run: it must be named by a
+    // filter if there are any, and must not be excluded.
+    pub fn selects(&self, prefix: &str) -> bool {
+        let mut included = self.filters().filter(|f| !f.exclude).peekable();
+        let named = included.peek().is_none() || included.any(|f| f.prefix == prefix);
+
+        named && !self.filters().any(|f| f.exclude && f.prefix == prefix)
+    }
+
+    pub fn filter_for(&self, prefix: &str) -> Option<&Filter> {
+        self.filters().find(|f| !f.exclude && f.prefix == prefix)
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.exclude {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.prefix)?;
+        for flag in &self.flags {
+            write!(f, "+{flag}")?;
+        }
+        match &self.args {
+            Some(args) if args.contains(|c: char| c.is_whitespace() || c == '"') => {
+                let escaped = args.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, ":\"{escaped}\"")
+            }
+            Some(args) => write!(f, ":{args}"),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Self {
+        Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn parse(mut self) -> Query {
+        let mut nodes = Vec::new();
+
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+            let Some(c) = self.peek() else { break };
+
+            let start = self.pos;
+            let node = match (c, self.peek_at(1)) {
+                ('"', _) => Node::Term(Term {
+                    text: self.quoted(),
+                    quoted: true,
+                    span: start..self.pos,
+                }),
+                ('@', Some(next)) if is_prefix_char(next) => self.filter(false),
+                ('-', Some('@')) if self.peek_at(2).is_some_and(is_prefix_char) => {
+                    self.pos += 1;
+                    self.filter(true)
+                }
+                _ => self.word(),
+            };
+
+            nodes.push(node);
+        }
+
+        Query { nodes }
+    }
+
+    // Consumes a `"..."` phrase, returning its contents. An unterminated quote runs to the end.
+    fn quoted(&mut self) -> String {
+        self.pos += 1;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '"' => break,
+                '\\' if matches!(self.peek(), Some('"' | '\\')) => {
+                    text.push(self.chars[self.pos]);
+                    self.pos += 1;
+                }
+                c => text.push(c),
+            }
+        }
+        text
+    }
+
+    // A value following `:`, either quoted or up to the next whitespace.
+    fn value(&mut self) -> String {
+        if self.peek() == Some('"') {
+            return self.quoted();
+        }
+
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn filter(&mut self, exclude: bool) -> Node {
+        let start = self.pos - exclude as usize;
+        let prefix_start = self.pos;
+        self.pos += 1;
+        while self.peek().is_some_and(is_prefix_char) {
+            self.pos += 1;
+        }
+        let prefix = self.chars[prefix_start..self.pos].iter().collect();
+
+        let mut flags = Vec::new();
+        while self.peek() == Some('+') && self.peek_at(1).is_some_and(is_prefix_char) {
+            self.pos += 1;
+            let flag_start = self.pos;
+            while self.peek().is_some_and(is_prefix_char) {
+                self.pos += 1;
+            }
+            flags.push(self.chars[flag_start..self.pos].iter().collect());
+        }
+
+        let args = match self.peek() {
+            Some(':') => {
+                self.pos += 1;
+                Some(self.value()).filter(|args| !args.is_empty())
+            }
+            Some(c) if !c.is_whitespace() => {
+                // Something like `@foo.bar` is a plain word, not a filter.
+                self.pos = start;
+                return self.word();
+            }
+            _ => None,
+        };
+
+        Node::Filter(Filter {
+            prefix,
+            flags,
+            args,
+            exclude,
+            span: start..self.pos,
+        })
+    }
+
+    fn word(&mut self) -> Node {
+        let start = self.pos;
+        while self.peek().is_some_and(is_key_char) {
+            self.pos += 1;
+        }
+
+        // `key:value` for known keys, but not `a::b` or `http://…`
+        let key: String = self.chars[start..self.pos].iter().collect();
+        if MODIFIER_KEYS.contains(&key.as_str())
+            && self.peek() == Some(':')
+            && self
+                .peek_at(1)
+                .is_some_and(|c| !c.is_whitespace() && c != ':' && c != '/')
+        {
+            self.pos += 1;
+            let value = self.value();
+            return Node::Modifier(Modifier {
+                key,
+                value,
+                span: start..self.pos,
+            });
+        }
+
+        while self.peek().is_some_and(|c| !c.is_whitespace()) {
+            self.pos += 1;
+        }
+        Node::Term(Term {
+            text: self.chars[start..self.pos].iter().collect(),
+            quoted: false,
+            span: start..self.pos,
+        })
+    }
+}
+
+fn is_prefix_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Byte offset of the `index`th char of `s`, clamped to its length.
+pub fn byte_offset(s: &str, index: usize) -> usize {
+    s.char_indices().nth(index).map_or(s.len(), |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nodes in a compact form: filters as they display, `'text'` for words, `"text"` for
+    // phrases and `key=value` for modifiers.
+    fn nodes(input: &str) -> Vec<String> {
+        Query::parse(input)
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::Filter(filter) => filter.to_string(),
+                Node::Term(Term {
+                    text, quoted: true, ..
+                }) => format!("\"{text}\""),
+                Node::Term(term) => format!("'{}'", term.text),
+                Node::Modifier(m) => format!("{}={}", m.key, m.value),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_queries() {
+        let cases: &[(&str, &[&str])] = &[
+            ("", &[]),
+            ("rust lang", &["'rust'", "'lang'"]),
+            (r#""rust lang" book"#, &["\"rust lang\"", "'book'"]),
+            (r#""say \"hi\" \\ bye""#, &[r#""say "hi" \ bye""#]),
+            (r#""unterminated phrase"#, &["\"unterminated phrase\""]),
+            ("@wi rust", &["@wi", "'rust'"]),
+            ("-@wi rust", &["-@wi", "'rust'"]),
+            ("@wi+text:de rust", &["@wi+text:de", "'rust'"]),
+            (
+                r#"@rg:"~/my notes" todo"#,
+                &[r#"@rg:"~/my notes""#, "'todo'"],
+            ),
+            ("@foo.bar", &["'@foo.bar'"]),
+            ("a-@wi", &["'a-@wi'"]),
+            ("ext:rs main", &["ext=rs", "'main'"]),
+            (r#"limit:"5""#, &["limit=5"]),
+            ("10:30 Re:Zero", &["'10:30'", "'Re:Zero'"]),
+            (
+                "ext: std::io https://x.org",
+                &["'ext:'", "'std::io'", "'https://x.org'"],
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(nodes(input), *expected, "parsing {input:?}");
+        }
+    }
+
+    #[test]
+    fn spans_are_char_ranges() {
+        let query = Query::parse("héllo @wi:dé ext:rs");
+        let spans: Vec<_> = query
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::Filter(f) => f.span.clone(),
+                Node::Term(t) => t.span.clone(),
+                Node::Modifier(m) => m.span.clone(),
+            })
+            .collect();
+        assert_eq!(spans, [0..5, 6..12, 13..19]);
+        assert_eq!(query.text(), "héllo");
+    }
+
+    #[test]
+    fn selects_engines() {
+        let query = Query::parse("@wi -@rg rust");
+        assert!(query.selects("@wi"));
+        assert!(!query.selects("@rg"));
+        assert!(!query.selects("@fzf"));
+
+        let query = Query::parse("-@rg rust");
+        assert!(query.selects("@fzf"));
+        assert!(!query.selects("@rg"));
+    }
+
+    #[test]
+    fn byte_offsets_clamp() {
+        let cases = [
+            ("", 0, 0),
+            ("abc", 1, 1),
+            ("héllo", 2, 3),
+            ("héllo", 5, 6),
+            ("héllo", 9, 6),
+        ];
+        for (s, index, expected) in cases {
+            assert_eq!(byte_offset(s, index), expected, "{s:?}[{index}]");
+        }
+    }
+
+    #[test]
+    fn filters_round_trip_through_display() {
+        let cases = [
+            "@wi",
+            "-@wi",
+            "@wi+text+title:de",
+            r#"@rg:"~/my notes""#,
+            r#"@rg:"say \"hi\" \\o/""#,
+            r#"@rg:"quote\"d""#,
+        ];
+        for input in cases {
+            let query = Query::parse(input);
+            let filter = query.filters().next().unwrap();
+            assert_eq!(filter.to_string(), input);
+            assert_eq!(Query::parse(&filter.to_string()), query);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// A local HTTP server for the tests of the engines and their HTTP plumbing.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone)]
+pub struct Reply {
+    head: String,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    // `status` is the code and reason, e.g. "404 Not Found".
+    pub fn new(status: &str) -> Self {
+        Reply {
+            head: status.to_string(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.head.push_str(&format!("\r\n{name}: {value}"));
+
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+
+        self
+    }
+}
+
+// Answers the `n`th request on a local port with `handler(n, request)`. Returns the server's
+// `http://host:port` and the number of requests it has answered so far.
+pub fn serve(
+    handler: impl Fn(usize, &str) -> Reply + Send + 'static,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0; 8192];
+            let len = stream.read(&mut buf).unwrap_or(0);
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let reply = handler(n, &String::from_utf8_lossy(&buf[..len]));
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                reply.head,
+                reply.body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&reply.body);
+        }
+    });
+    (format!("http://{addr}"), calls)
+}
+
+// A handler answering the `n`th request with `replies[n]`, repeating the last one.
+pub fn in_turn(replies: Vec<Reply>) -> impl Fn(usize, &str) -> Reply + Send + 'static {
+    move |n, _| replies[n.min(replies.len() - 1)].clone()
+}
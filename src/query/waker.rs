@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "gui")]
+use std::time::Duration;
+
+/// Tells whoever reads a query's responses that more have arrived. A burst of responses
+/// wakes the reader once, until it calls [`Waker::reset`] to say it has caught up.
+pub struct Waker {
+    wake: Box<dyn Fn() + Send + Sync>,
+    pending: AtomicBool,
+}
+
+impl std::fmt::Debug for Waker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Waker")
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+impl Waker {
+    pub fn new(wake: impl Fn() + Send + Sync + 'static) -> Self {
+        Waker {
+            wake: Box::new(wake),
+            pending: AtomicBool::new(false),
+        }
+    }
+
+    /// Repaints `ctx` within `budget` of the first response, so the rest of a burst lands in
+    /// the same frame.
+    #[cfg(feature = "gui")]
+    pub fn repaint(ctx: egui::Context, budget: Duration) -> Self {
+        Self::new(move || ctx.request_repaint_after(budget))
+    }
+
+    pub fn wake(&self) {
+        if !self.pending.swap(true, Ordering::AcqRel) {
+            (self.wake)();
+        }
+    }
+
+    /// Call before reading responses, so that any arriving afterwards wake again.
+    pub fn reset(&self) {
+        self.pending.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn coalesces_wakes_until_reset() {
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker = Waker::new({
+            let wakes = wakes.clone();
+            move || {
+                wakes.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        for _ in 0..100 {
+            waker.wake();
+        }
+        assert_eq!(wakes.load(Ordering::Relaxed), 1);
+
+        waker.reset();
+        waker.reset();
+        assert_eq!(wakes.load(Ordering::Relaxed), 1);
+        waker.wake();
+        waker.wake();
+        assert_eq!(wakes.load(Ordering::Relaxed), 2);
+    }
+}
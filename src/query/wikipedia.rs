@@ -1,12 +1,15 @@
-use crate::query::SearchEngine;
-use crate::response::QueryResponse;
-use egui::Ui;
-use flume::Sender;
+use crate::query::cache::ResultData;
+use crate::query::http::{self, DEFAULT_TIMEOUT, HttpConfig, HttpPolicy};
+use crate::query::oauth::{Credentials, TokenSource};
+use crate::query::{SearchEngine, SearchRequest};
+use crate::response::{ActionKind, Field, Icon, Payload, QueryResponse, ResultItem};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::Duration;
 use strfmt::strfmt;
-use surf::{Client, Config, Url};
+use surf::{Client, Url};
 
 lazy_static! {
     pub static ref WIKIMEDIA_CLIENT_ID: Option<String> = std::env::var("WIKIMEDIA_CLIENT_ID").ok();
@@ -17,6 +20,88 @@ lazy_static! {
 }
 
 pub const WIKIMEDIA_URL: &str = "https://api.wikimedia.org";
+pub const WIKIMEDIA_TOKEN_URL: &str = "https://meta.wikimedia.org/w/rest.php/oauth2/access_token";
+
+const ICON: &str = "󰖬";
+
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Title,
+    Text,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WikipediaConfig {
+    pub language: String,
+    pub project: String,
+    pub limit: Option<u8>,
+    pub mode: SearchMode,
+}
+
+impl Default for WikipediaConfig {
+    fn default() -> Self {
+        WikipediaConfig {
+            language: "en".to_string(),
+            project: "wikipedia".to_string(),
+            limit: None,
+            mode: SearchMode::default(),
+        }
+    }
+}
+
+impl WikipediaConfig {
+    // Applies per-query overrides: `@wi:de` or `@wi:de.wiktionary` for the language and
+    // project, `@wi+text`/`@wi+title` for the mode, and a `limit:N` modifier.
+    pub fn with_request(&self, request: &SearchRequest) -> Self {
+        let mut config = self.clone();
+
+        if let Some(args) = &request.args {
+            match args.split_once('.') {
+                Some((language, project)) => {
+                    config.language = language.to_string();
+                    config.project = project.to_string();
+                }
+                None => config.language = args.to_string(),
+            }
+        }
+
+        for flag in &request.flags {
+            match flag.as_str() {
+                "text" => config.mode = SearchMode::Text,
+                "title" => config.mode = SearchMode::Title,
+                _ => log::warn!("Unknown wikipedia flag: +{flag}"),
+            }
+        }
+
+        if let Some(limit) = request.modifier("limit") {
+            match limit.parse() {
+                Ok(limit) => config.limit = Some(limit),
+                Err(e) => log::warn!("Invalid limit `{limit}`: {e}"),
+            }
+        }
+
+        config
+    }
+
+    pub fn host(&self) -> String {
+        match self.project.as_str() {
+            "commons" | "meta" | "species" => format!("{}.wikimedia.org", self.project),
+            "wikidata" => "www.wikidata.org".to_string(),
+            project => format!("{}.{project}.org", self.language),
+        }
+    }
+
+    pub fn article_url(&self, key: &str) -> anyhow::Result<Url> {
+        let mut url = Url::parse(&format!("https://{}/wiki", self.host()))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("invalid article url"))?
+            .push(key);
+        Ok(url)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchResults {
@@ -34,6 +119,13 @@ pub struct SearchResult {
     thumbnail: Option<Thumbnail>,
 }
 
+// A search result along with its article URL, which depends on the request.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Article {
+    page: SearchResult,
+    url: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Thumbnail {
     mimetype: String,
@@ -72,131 +164,287 @@ impl SearchTitle {
 
 #[derive(Debug)]
 pub struct WikipediaEngine {
+    config: WikipediaConfig,
     client: Option<Client>,
+    auth: Option<TokenSource>,
+    policy: HttpPolicy,
 }
 
 impl WikipediaEngine {
-    pub fn new() -> Self {
-        let client = (|| {
-            let mut config = Config::new()
-                .set_base_url(
-                    Url::parse(WIKIMEDIA_URL)
-                        .inspect_err(|e| log::error!("{e}"))
-                        .ok()?,
-                )
-                .set_timeout(Some(Duration::from_secs(5)));
-
-            if let Some(access_token) = &*WIKIMEDIA_ACCESS_TOKEN {
-                config = config
-                    .add_header("User-Agent", "Amoeba (me@arvinsk.org)")
-                    .inspect_err(|e| log::error!("{e}"))
-                    .ok()?
-                    .add_header("Authorization", format!("Bearer {access_token}"))
-                    .inspect_err(|e| log::error!("{e}"))
-                    .ok()?;
-            }
+    pub fn new(config: WikipediaConfig, http: &HttpConfig) -> Self {
+        let client = http::client(WIKIMEDIA_URL, DEFAULT_TIMEOUT, &BTreeMap::new(), http);
 
-            config.try_into().ok()
-        })();
+        Self {
+            config,
+            client,
+            auth: credentials().map(TokenSource::new),
+            policy: HttpPolicy::new(http),
+        }
+    }
+}
 
-        Self { client }
+// Client credentials take precedence, since their tokens can be refreshed.
+fn credentials() -> Option<Credentials> {
+    match (&*WIKIMEDIA_CLIENT_ID, &*WIKIMEDIA_CLIENT_SECRET) {
+        (Some(client_id), Some(client_secret)) => Some(Credentials::ClientCredentials {
+            token_url: WIKIMEDIA_TOKEN_URL.to_string(),
+            client_id: client_id.clone(),
+            client_secret: client_secret.clone(),
+        }),
+        _ => WIKIMEDIA_ACCESS_TOKEN.clone().map(Credentials::Static),
     }
 }
 
 #[async_trait::async_trait]
 impl SearchEngine for WikipediaEngine {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         "wikipedia"
     }
 
-    fn prefix(&self) -> &'static str {
+    fn prefix(&self) -> &str {
         "@wi"
     }
 
-    fn icon(&self) -> Box<dyn Fn(&mut Ui) -> egui::Response + Send> {
-        Box::new(|ui| ui.monospace("󰖬"))
+    fn description(&self) -> &str {
+        "Search Wikipedia articles"
+    }
+
+    fn icon(&self) -> Icon {
+        Icon::Glyph(ICON.into())
     }
 
-    async fn search(&self, query: &str, channel: Sender<QueryResponse>) -> anyhow::Result<()> {
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    async fn fetch(&self, request: &SearchRequest) -> anyhow::Result<Vec<ResultData>> {
+        let query = request.text.as_str();
         log::info!("WikipediaEngine Query: {}", query);
 
         futures_timer::Delay::new(Duration::from_millis(720)).await;
 
         log::info!("Making request");
 
-        let search = SearchTitle {
-            project: "wikipedia".to_string(),
-            language: "en".to_string(),
-            query: query.to_string(),
-            limit: None,
+        let config = self.config.with_request(request);
+
+        let Some(client) = &self.client else {
+            return Ok(Vec::new());
+        };
+
+        let endpoint = match config.mode {
+            SearchMode::Title => SearchTitle::ENDPOINT,
+            SearchMode::Text => SearchContent::ENDPOINT,
         };
+        let builder = client.get(strfmt!(endpoint, project => config.project.clone(), language => config.language.clone())?);
+        let builder = match config.mode {
+            SearchMode::Title => builder.query(&SearchTitle {
+                project: config.project.clone(),
+                language: config.language.clone(),
+                query: query.to_string(),
+                limit: config.limit,
+            }),
+            SearchMode::Text => builder.query(&SearchContent {
+                project: config.project.clone(),
+                language: config.language.clone(),
+                query: query.to_string(),
+                limit: config.limit,
+            }),
+        };
+        let request = builder.map_err(|err| anyhow::anyhow!(err))?.build();
 
-        if let Some(client) = &self.client {
-            let request = client.get(strfmt!(SearchTitle::ENDPOINT, project => search.project.clone(), language => search.language.clone())?)
-                .query(&search)
-                .map_err(|err| anyhow::anyhow!(err))?
-                .build();
-
-            let mut response = client
-                .send(request)
-                .await
-                .map_err(|err| anyhow::anyhow!(err))?;
-
-            log::trace!("Response: {response:?}");
-
-            let res: SearchResults = response
-                .body_json()
-                .await
-                .map_err(|err| anyhow::anyhow!(err))?;
-
-            for (i, res) in res.pages.into_iter().enumerate() {
-                let icon = self.icon();
-                let send_res = channel
-                    .send_async(QueryResponse::new(
-                        {
-                            let res = res.clone();
-                            Box::new(move |ui: &mut egui::Ui| {
-                                icon(ui);
-
-                                ui.add(
-                                    egui::Label::new(egui::RichText::new(
-                                        format!(
-                                            "({title}) {desc}",
-                                            title = res.title,
-                                            desc = if let Some(desc) = res.description.as_ref() {
-                                                desc
-                                            } else {
-                                                ""
-                                            }
-                                        )
-                                        .trim(),
-                                    ))
-                                    .wrap_mode(egui::TextWrapMode::Wrap),
-                                )
-                            })
-                        },
-                        {
-                            let search = search.clone();
-                            Box::new(move |ui: &mut egui::Ui| {
-                                ui.ctx().send_cmd(egui::OutputCommand::OpenUrl(
-                                    egui::OpenUrl::new_tab(format!(
-                                        "https://{language}.wikipedia.org/wiki/{title}",
-                                        language = &search.language,
-                                        title = &res.key
-                                    )),
-                                ));
-                            })
-                        },
-                        5 - (i as i64),
-                    ))
-                    .await;
-
-                if let Err(err) = send_res {
-                    return Err(anyhow::anyhow!("Err: {}", err));
+        let mut response = self
+            .policy
+            .send(request, |request| async {
+                match &self.auth {
+                    Some(auth) => auth.send(client, request).await,
+                    None => client
+                        .send(request)
+                        .await
+                        .map_err(|err| anyhow::anyhow!(err)),
                 }
+            })
+            .await?;
+
+        log::trace!("Response: {response:?}");
+        if !response.status().is_success() {
+            anyhow::bail!("Wikimedia API returned {}", response.status());
+        }
+
+        let res: SearchResults = response
+            .body_json()
+            .await
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        res.pages
+            .into_iter()
+            .map(|page| {
+                let url = config.article_url(&page.key)?.to_string();
+                Ok(Arc::new(Article { page, url }) as ResultData)
+            })
+            .collect()
+    }
+
+    fn respond(&self, data: &ResultData, rank: usize) -> Option<QueryResponse> {
+        let article = data.clone().downcast::<Article>().ok()?;
+        let Article { page: res, url } = (*article).clone();
+        let icon = match &res.thumbnail {
+            Some(thumbnail) => Icon::Image {
+                url: thumbnail.url.clone(),
+                fallback: ICON.to_string(),
+            },
+            None => Icon::Glyph(ICON.to_string()),
+        };
+        let mut item = ResultItem::new(icon, res.title.as_str());
+
+        let mut excerpt = String::new();
+        let mut matches = Vec::new();
+        for (text, matched) in excerpt_segments(&res.excerpt) {
+            let start = excerpt.chars().count();
+            excerpt.push_str(&text);
+            if matched {
+                matches.push(start..start + text.chars().count());
+            }
+        }
+        // Title searches echo the title back as the excerpt, in which case the description
+        // stands in for it.
+        if !excerpt.trim().is_empty() && excerpt != res.title {
+            item = item.with_subtitle(excerpt);
+            for range in matches {
+                item = item.with_match(Field::Subtitle, range);
+            }
+            if let Some(description) = res.description {
+                item = item.with_badge(description);
+            }
+        } else if let Some(description) = res.description {
+            item = item.with_subtitle(description);
+        }
+
+        let item = item
+            .with_metadata("url", &url)
+            .with_action("Open", ActionKind::OpenUrl(url.clone()))
+            .with_action("Copy link", ActionKind::Copy(url));
+
+        Some(QueryResponse::new(item, 5 - (rank as i64)).with_payload(Payload::shared(article)))
+    }
+}
+
+// Splits an excerpt into text runs, flagging those wrapped in `<span class="searchmatch">`.
+// Other tags are dropped and HTML entities decoded.
+fn excerpt_segments(html: &str) -> Vec<(String, bool)> {
+    let mut segments: Vec<(String, bool)> = Vec::new();
+    let mut matched = false;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let tag = rest
+            .find('<')
+            .and_then(|start| Some((start, start + rest[start..].find('>')?)));
+        let text = match tag {
+            Some((start, _)) => &rest[..start],
+            None => rest,
+        };
+
+        let text = decode_entities(text);
+        if !text.is_empty() {
+            match segments.last_mut() {
+                Some((last, last_matched)) if *last_matched == matched => last.push_str(&text),
+                _ => segments.push((text, matched)),
             }
         }
 
-        Ok(())
+        let Some((start, end)) = tag else { break };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with("span") && tag.contains("searchmatch") {
+            matched = true;
+        } else if tag.starts_with("/span") {
+            matched = false;
+        }
+    }
+
+    segments
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#')?.parse().ok())
+                .and_then(char::from_u32),
+        });
+
+        match (c, entity) {
+            (Some(c), Some(entity)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excerpt_highlights_search_matches() {
+        let segments = excerpt_segments(
+            r#"The <span class="searchmatch">Rust</span> &quot;book&quot; &amp; <b>more</b> <span class="searchmatch">rust</span>"#,
+        );
+        assert_eq!(
+            segments,
+            vec![
+                ("The ".to_string(), false),
+                ("Rust".to_string(), true),
+                (" \"book\" & more ".to_string(), false),
+                ("rust".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn request_overrides_language_project_and_mode() {
+        let query = crate::query::parser::Query::parse("@wi+text:de.wiktionary Haus limit:5");
+        let request = SearchRequest::new(&query, "@wi");
+        let config = WikipediaConfig::default().with_request(&request);
+
+        assert_eq!(config.language, "de");
+        assert_eq!(config.project, "wiktionary");
+        assert_eq!(config.mode, SearchMode::Text);
+        assert_eq!(config.limit, Some(5));
+        assert_eq!(
+            config.article_url("Haus?").unwrap().as_str(),
+            "https://de.wiktionary.org/wiki/Haus%3F"
+        );
+    }
+
+    #[test]
+    fn excerpt_keeps_unknown_entities_and_broken_tags() {
+        assert_eq!(
+            excerpt_segments("a &bogus; &#233;&#x4E2D; b < c"),
+            vec![("a &bogus; é中 b < c".to_string(), false)]
+        );
     }
 }
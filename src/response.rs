@@ -1,46 +1,499 @@
+#[cfg(feature = "gui")]
+use crate::keymap::Command;
+use crate::query::Engine;
+#[cfg(feature = "gui")]
+use crate::query::{images, parser::byte_offset};
+#[cfg(feature = "gui")]
 use crate::theme::Theme;
-use egui::{TextStyle, Ui};
+#[cfg(feature = "gui")]
+use egui::text::{LayoutJob, TextFormat};
+#[cfg(feature = "gui")]
+use egui::{Context, TextStyle, Ui};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 
+#[cfg(feature = "gui")]
+const IMAGE_ICON_SIZE: egui::Vec2 = egui::vec2(40., 40.);
+
+/// Draws a result in place of the standard layout.
+#[cfg(feature = "gui")]
+pub type Widget = Box<dyn Fn(&mut Ui, &ResultItem) -> egui::Response + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Icon {
+    /// A Nerd Font glyph.
+    Glyph(String),
+    /// An image, showing `fallback` until it has loaded or if it can't be.
+    Image { url: String, fallback: String },
+}
+
+#[cfg(feature = "gui")]
+impl Icon {
+    /// Draws the icon, or its fallback glyph when the image isn't available.
+    pub fn ui(&self, ui: &mut Ui) -> egui::Response {
+        match self {
+            Icon::Glyph(glyph) => ui.monospace(glyph),
+            Icon::Image { url, fallback } => images::loader(ui.ctx())
+                .and_then(|images| images.show(ui, url, IMAGE_ICON_SIZE))
+                .unwrap_or_else(|| ui.monospace(fallback)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Subtitle,
+}
+
+/// A span of `field` matching the query, in chars.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MatchRange {
+    pub field: Field,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    OpenUrl(String),
+    OpenPath(PathBuf),
+    Copy(String),
+    /// Run by the engine that found the result, through
+    /// [`SearchEngine::run_action`](crate::query::SearchEngine::run_action), with the
+    /// response's payload.
+    Engine(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResultAction {
+    pub label: String,
+    pub kind: ActionKind,
+}
+
+impl ResultAction {
+    pub fn new(label: impl Into<String>, kind: ActionKind) -> Self {
+        ResultAction {
+            label: label.into(),
+            kind,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn run(&self, ctx: &Context) {
+        match &self.kind {
+            ActionKind::OpenUrl(url) => {
+                ctx.send_cmd(egui::OutputCommand::OpenUrl(egui::OpenUrl::new_tab(url)))
+            }
+            ActionKind::OpenPath(path) => match std::path::absolute(path).ok().and_then(file_url) {
+                Some(url) => {
+                    ctx.send_cmd(egui::OutputCommand::OpenUrl(egui::OpenUrl::same_tab(url)))
+                }
+                None => log::warn!("Can't open {path:?}"),
+            },
+            ActionKind::Copy(text) => ctx.copy_text(text.clone()),
+            ActionKind::Engine(name) => {
+                log::warn!("Engine action {name} can only run through its response")
+            }
+        }
+    }
+}
+
+/// What a result is, independent of how it's drawn. The first action runs on execute, the
+/// second on the alternate execute, and all but the first are offered in the context menu.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResultItem {
+    pub icon: Icon,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub badges: Vec<String>,
+    pub matches: Vec<MatchRange>,
+    pub metadata: BTreeMap<String, String>,
+    pub actions: Vec<ResultAction>,
+    /// The icons of other engines that found the same result and were merged into it.
+    pub sources: Vec<Icon>,
+}
+
+impl ResultItem {
+    pub fn new(icon: Icon, title: impl Into<String>) -> Self {
+        ResultItem {
+            icon,
+            title: title.into(),
+            subtitle: None,
+            badges: Vec::new(),
+            matches: Vec::new(),
+            metadata: BTreeMap::new(),
+            actions: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    pub fn with_subtitle(mut self, subtitle: impl Into<String>) -> Self {
+        self.subtitle = Some(subtitle.into());
+
+        self
+    }
+
+    pub fn with_badge(mut self, badge: impl Into<String>) -> Self {
+        self.badges.push(badge.into());
+
+        self
+    }
+
+    pub fn with_match(mut self, field: Field, range: Range<usize>) -> Self {
+        self.matches.push(MatchRange { field, range });
+
+        self
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+
+        self
+    }
+
+    pub fn with_action(mut self, label: impl Into<String>, kind: ActionKind) -> Self {
+        self.actions.push(ResultAction::new(label, kind));
+
+        self
+    }
+
+    /// What copying the result copies: the first text to copy, or else what it opens.
+    pub fn copy_text(&self) -> Option<String> {
+        let copy = self.actions.iter().find_map(|action| match &action.kind {
+            ActionKind::Copy(text) => Some(text.clone()),
+            _ => None,
+        });
+        copy.or_else(|| {
+            self.actions.iter().find_map(|action| match &action.kind {
+                ActionKind::OpenUrl(url) => Some(url.clone()),
+                ActionKind::OpenPath(path) => Some(path.to_string_lossy().to_string()),
+                ActionKind::Copy(_) | ActionKind::Engine(_) => None,
+            })
+        })
+    }
+
+    /// What the result is about, to tell when engines found the same thing: the path or URL
+    /// its first open action opens.
+    pub fn canonical_key(&self) -> Option<String> {
+        self.actions.iter().find_map(|action| match &action.kind {
+            ActionKind::OpenPath(path) => file_url(canonical_path(path)),
+            ActionKind::OpenUrl(url) => {
+                let mut url = surf::Url::parse(url).ok()?;
+                url.set_fragment(None);
+                Some(url.as_str().trim_end_matches('/').to_string())
+            }
+            ActionKind::Copy(_) | ActionKind::Engine(_) => None,
+        })
+    }
+
+    /// Folds in what `other` adds: its engine icon, badges, metadata and actions, and its
+    /// subtitle if this has none.
+    pub fn merge(&mut self, other: ResultItem) {
+        for icon in std::iter::once(other.icon).chain(other.sources) {
+            let icon = match icon {
+                Icon::Image { fallback, .. } => Icon::Glyph(fallback),
+                icon => icon,
+            };
+            let own = match &self.icon {
+                Icon::Image { fallback, .. } => *fallback == icon_glyph(&icon),
+                Icon::Glyph(glyph) => *glyph == icon_glyph(&icon),
+            };
+            if !own && !self.sources.contains(&icon) {
+                self.sources.push(icon);
+            }
+        }
+
+        if self.subtitle.is_none() && other.subtitle.is_some() {
+            self.subtitle = other.subtitle;
+            self.matches.extend(
+                other
+                    .matches
+                    .into_iter()
+                    .filter(|m| m.field == Field::Subtitle),
+            );
+        }
+        for badge in other.badges {
+            if !self.badges.contains(&badge) {
+                self.badges.push(badge);
+            }
+        }
+        for (key, value) in other.metadata {
+            self.metadata.entry(key).or_insert(value);
+        }
+        // Engine actions need the other response's engine and payload, which aren't kept.
+        for action in other.actions {
+            if matches!(action.kind, ActionKind::Engine(_)) {
+                continue;
+            }
+            // Each engine has its own take on e.g. the path to copy, of which the first is kept.
+            let kind = std::mem::discriminant(&action.kind);
+            if !self
+                .actions
+                .iter()
+                .any(|own| std::mem::discriminant(&own.kind) == kind)
+            {
+                self.actions.push(action);
+            }
+        }
+    }
+
+    /// The standard layout: the icon, the title followed by badges, and the subtitle below,
+    /// with matches highlighted.
+    #[cfg(feature = "gui")]
+    pub fn ui(&self, ui: &mut Ui, theme: &Theme) -> egui::Response {
+        self.icon.ui(ui);
+        for source in &self.sources {
+            ui.label(egui::RichText::new(icon_glyph(source)).monospace().small());
+        }
+
+        ui.vertical(|ui| {
+            ui.horizontal_wrapped(|ui| {
+                let strong = ui.visuals().strong_text_color();
+                ui.label(self.highlighted(ui, theme, Field::Title, &self.title, strong));
+                for badge in &self.badges {
+                    ui.label(egui::RichText::new(badge).small().weak());
+                }
+            });
+
+            if let Some(subtitle) = &self.subtitle {
+                let weak = ui.visuals().weak_text_color();
+                ui.add(
+                    egui::Label::new(self.highlighted(ui, theme, Field::Subtitle, subtitle, weak))
+                        .wrap_mode(egui::TextWrapMode::Wrap),
+                );
+            }
+        })
+        .response
+    }
+
+    #[cfg(feature = "gui")]
+    fn highlighted(
+        &self,
+        ui: &Ui,
+        theme: &Theme,
+        field: Field,
+        text: &str,
+        color: egui::Color32,
+    ) -> LayoutJob {
+        let font_id = TextStyle::Body.resolve(ui.style());
+        let normal = TextFormat::simple(font_id, color);
+        let matched = TextFormat {
+            background: theme.selection_bg_fill.to(),
+            ..normal.clone()
+        };
+
+        let mut ranges: Vec<_> = self
+            .matches
+            .iter()
+            .filter(|m| m.field == field)
+            .map(|m| byte_offset(text, m.range.start)..byte_offset(text, m.range.end))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut job = LayoutJob::default();
+        let mut pos = 0;
+        for range in ranges {
+            let start = range.start.max(pos);
+            if start >= range.end {
+                continue;
+            }
+            job.append(&text[pos..start], 0., normal.clone());
+            job.append(&text[start..range.end], 0., matched.clone());
+            pos = range.end;
+        }
+        job.append(&text[pos..], 0., normal);
+        job
+    }
+}
+
+fn icon_glyph(icon: &Icon) -> &str {
+    match icon {
+        Icon::Glyph(glyph) => glyph,
+        Icon::Image { fallback, .. } => fallback,
+    }
+}
+
+// `path`, which must be absolute, as a `file://` URL with anything that needs it encoded.
+fn file_url(path: impl AsRef<Path>) -> Option<String> {
+    surf::Url::from_file_path(path).ok().map(String::from)
+}
+
+// `path` made absolute with `..` resolved, following symlinks when it exists.
+fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(path) = std::fs::canonicalize(path) {
+        return path;
+    }
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+/// Structured state an engine attaches to a response, such as the path or article it's
+/// about, for when it later runs an action, builds a preview or records history.
+#[derive(Clone)]
+pub struct Payload {
+    value: Arc<dyn Any + Send + Sync>,
+    kind: &'static str,
+    encode: fn(&(dyn Any + Send + Sync)) -> serde_json::Result<serde_json::Value>,
+}
+
+impl std::fmt::Debug for Payload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Payload").field("kind", &self.kind).finish()
+    }
+}
+
+impl Payload {
+    pub fn new<T: Serialize + Send + Sync + 'static>(value: T) -> Self {
+        Self::shared(Arc::new(value))
+    }
+
+    /// Wraps a value that is already shared, such as cached [`ResultData`](crate::query::cache::ResultData).
+    pub fn shared<T: Serialize + Send + Sync + 'static>(value: Arc<T>) -> Self {
+        Payload {
+            value,
+            kind: std::any::type_name::<T>(),
+            encode: |value| {
+                let value = value
+                    .downcast_ref::<T>()
+                    .expect("payload encoder matches its value");
+                serde_json::to_value(value)
+            },
+        }
+    }
+
+    /// The name of the payload's type.
+    pub fn kind(&self) -> &str {
+        self.kind
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// Encodes the payload so it can cross a process boundary or be stored.
+    pub fn persist(&self) -> serde_json::Result<PersistedPayload> {
+        Ok(PersistedPayload {
+            kind: self.kind.to_string(),
+            value: (self.encode)(self.value.as_ref())?,
+        })
+    }
+}
+
+/// A [`Payload`] as JSON, tagged with its type name. Type names are only stable within a
+/// build, so persisted payloads are meant for the same binary on the other end.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedPayload {
+    pub kind: String,
+    pub value: serde_json::Value,
+}
+
+impl PersistedPayload {
+    /// Decodes the payload as a `T`, or `None` if it was persisted from another type.
+    pub fn restore<T: Serialize + DeserializeOwned + Send + Sync + 'static>(
+        &self,
+    ) -> Option<Payload> {
+        if self.kind != std::any::type_name::<T>() {
+            return None;
+        }
+        serde_json::from_value::<T>(self.value.clone())
+            .inspect_err(|e| log::warn!("Failed to restore {} payload: {e}", self.kind))
+            .ok()
+            .map(Payload::new)
+    }
+}
+
+/// A result row as it's shown: the result plus how to draw it and where it ranks.
+/// Rows are ordered by descending `priority`.
 pub struct QueryResponse {
+    pub item: ResultItem,
+    #[cfg(feature = "gui")]
+    pub widget: Option<Widget>,
     pub duration: Option<Duration>,
-    pub display: Box<dyn Fn(&mut Ui) -> egui::Response + Send>,
-    pub action: Box<dyn Fn(&mut Ui) -> () + Send>,
     pub priority: i64,
-    extra_state: Option<Vec<u8>>,
+    /// The engines that found the result, the one the item came from first. Set as the
+    /// response is relayed from them.
+    pub engines: Vec<String>,
+    // The engine the item, and so its payload and engine actions, came from.
+    pub(crate) source: Option<Engine>,
+    // The item's canonical key, worked out off the UI thread by `with_key`.
+    key: Option<String>,
+    payload: Option<Payload>,
     uuid: Uuid,
 }
 
 impl std::fmt::Debug for QueryResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("QueryResponse")
+        let mut debug = f.debug_struct("QueryResponse");
+        debug.field("item", &self.item);
+        #[cfg(feature = "gui")]
+        debug.field("widget", &self.widget.as_ref().map(|_| ".."));
+        debug
             .field("duration", &self.duration)
-            .field("display", &"..")
             .field("priority", &self.priority)
+            .field("engines", &self.engines)
+            .field("payload", &self.payload)
             .field("uuid", &self.uuid)
             .finish()
     }
 }
 
 impl QueryResponse {
-    pub fn new(
-        widget: Box<impl Fn(&mut Ui) -> egui::Response + Send + 'static>,
-        action: Box<impl Fn(&mut Ui) -> () + Send + 'static>,
-        priority: i64,
-    ) -> Self {
+    pub fn new(item: ResultItem, priority: i64) -> Self {
         Self {
+            item,
+            #[cfg(feature = "gui")]
+            widget: None,
             duration: None,
-            display: widget,
-            action,
             priority,
-            extra_state: None,
+            engines: Vec::new(),
+            source: None,
+            key: None,
+            payload: None,
             uuid: Uuid::new_v4(),
         }
     }
 
-    pub fn with_extra_state(mut self, state: Vec<u8>) -> Self {
-        self.extra_state = Some(state);
+    /// Works out the key [`ResultList`] merges the response by. It can touch the file
+    /// system, so it's done as the response is relayed rather than when it's listed.
+    pub fn with_key(mut self) -> Self {
+        self.key = self.item.canonical_key();
+
+        self
+    }
+
+    pub fn with_payload(mut self, payload: Payload) -> Self {
+        self.payload = Some(payload);
+
+        self
+    }
+
+    /// Draws the result with `widget` instead of the standard layout.
+    #[cfg(feature = "gui")]
+    pub fn with_widget(
+        mut self,
+        widget: impl Fn(&mut Ui, &ResultItem) -> egui::Response + Send + 'static,
+    ) -> Self {
+        self.widget = Some(Box::new(widget));
 
         self
     }
@@ -55,15 +508,51 @@ impl QueryResponse {
         self.uuid
     }
 
-    pub fn get_extra_state(&self) -> Option<&Vec<u8>> {
-        self.extra_state.as_ref()
+    #[cfg(feature = "gui")]
+    pub fn row_id(&self, ui: &Ui) -> egui::Id {
+        ui.id().with(self.uuid)
     }
 
-    pub fn get_extra_state_mut(&mut self) -> Option<&mut Vec<u8>> {
-        self.extra_state.as_mut()
+    /// Merges a response from another engine for the same result. The higher priority one
+    /// leads, and results several engines agree on rank above either alone.
+    pub fn merge(&mut self, mut other: QueryResponse) {
+        if other.priority > self.priority {
+            std::mem::swap(&mut self.item, &mut other.item);
+            #[cfg(feature = "gui")]
+            std::mem::swap(&mut self.widget, &mut other.widget);
+            std::mem::swap(&mut self.payload, &mut other.payload);
+            std::mem::swap(&mut self.source, &mut other.source);
+            std::mem::swap(&mut self.engines, &mut other.engines);
+        }
+        self.priority = self.priority.max(other.priority).saturating_add(1);
+        self.duration = self.duration.max(other.duration);
+        self.item.merge(other.item);
+        for engine in other.engines {
+            if !self.engines.contains(&engine) {
+                self.engines.push(engine);
+            }
+        }
+    }
+
+    pub fn payload(&self) -> Option<&Payload> {
+        self.payload.as_ref()
+    }
+
+    /// The payload as a `T`, if it has one of that type.
+    pub fn payload_as<T: Any>(&self) -> Option<&T> {
+        self.payload.as_ref()?.downcast_ref()
+    }
+
+    /// Tells the engine the result came from that `query` was recorded in the history
+    /// because the result ran.
+    pub fn record(&self, query: &str) {
+        if let Some(engine) = &self.source {
+            engine.record(query, self.payload());
+        }
     }
 }
 
+#[cfg(feature = "gui")]
 fn format_duration(duration: Duration) -> String {
     match duration.as_millis() {
         0..5000 => format!("({i} ms)", i = duration.as_millis()),
@@ -71,21 +560,77 @@ fn format_duration(duration: Duration) -> String {
     }
 }
 
+#[cfg(feature = "gui")]
 impl QueryResponse {
-    pub(crate) fn ui(
+    /// Runs `command` on the response, returning whether it did anything.
+    pub fn run(&self, command: Command, ctx: &Context) -> bool {
+        let action = match command {
+            Command::Execute => self.item.actions.first(),
+            Command::ExecuteAlt => self.item.actions.get(1),
+            Command::Copy => {
+                let Some(text) = self.item.copy_text() else {
+                    return false;
+                };
+                ctx.copy_text(text);
+                return true;
+            }
+            _ => None,
+        };
+        action.is_some_and(|action| self.run_action(action, ctx))
+    }
+
+    /// Runs `action`, handing [`ActionKind::Engine`] actions to the engine the result came
+    /// from. Returns whether it ran.
+    pub fn run_action(&self, action: &ResultAction, ctx: &Context) -> bool {
+        let ActionKind::Engine(name) = &action.kind else {
+            action.run(ctx);
+            return true;
+        };
+        let Some(engine) = &self.source else {
+            log::warn!("No engine to run {name} for {}", self.item.title);
+            return false;
+        };
+        match engine.run_action(name, self.payload()) {
+            Ok(kind) => {
+                if let Some(kind) = kind {
+                    ResultAction::new(&action.label, kind).run(ctx);
+                }
+                true
+            }
+            Err(e) => {
+                log::error!("{} failed to run {name}: {e}", engine.name());
+                false
+            }
+        }
+    }
+
+    /// Buttons for the secondary actions, for a context menu.
+    pub fn secondary_menu(&self, ui: &mut Ui) {
+        for action in self.item.actions.iter().skip(1) {
+            if ui.button(&action.label).clicked() {
+                self.run_action(action, ui.ctx());
+                ui.close();
+            }
+        }
+    }
+
+    /// Shows the response as a result row, highlighted when `active`. `badge` is the
+    /// 1-based quick-execute index shown next to the row while the modifier is held.
+    pub fn ui(
         &self,
         ui: &mut Ui,
         panel: egui::Frame,
         theme: &Theme,
         width: f32,
         active: bool,
-    ) {
+        badge: Option<usize>,
+    ) -> egui::Response {
         let response = panel
             .show(ui, |ui| {
                 ui.set_width(width - (theme.margin.left + theme.margin.right) as f32);
                 ui.horizontal(|ui| {
                     let data = if let Some(duration) = self.duration {
-                        let small_style = TextStyle::Small.resolve(&ui.style());
+                        let small_style = TextStyle::Small.resolve(ui.style());
                         let formatted_duration = format_duration(duration);
                         let text = egui::RichText::new(&formatted_duration).small();
                         let text_size = ui
@@ -109,7 +654,10 @@ impl QueryResponse {
                                 ui.available_width() - size.x - theme.margin.x() as f32,
                             );
                         }
-                        (self.display)(ui)
+                        match &self.widget {
+                            Some(widget) => widget(ui, &self.item),
+                            None => self.item.ui(ui, theme),
+                        }
                     });
 
                     if let Some((text, size)) = data {
@@ -119,13 +667,343 @@ impl QueryResponse {
                 });
             })
             .response;
+        let response = ui.interact(response.rect, self.row_id(ui), egui::Sense::click());
 
-        if active && ui.ctx().input(|i| i.key_pressed(egui::Key::Enter)) {
-            (self.action)(ui)
+        if let Some(badge) = badge {
+            let center = egui::pos2(
+                response.rect.left() + theme.margin.left as f32 / 2.,
+                response.rect.center().y,
+            );
+            let radius = theme.margin.left as f32 / 2. - 2.;
+            ui.painter().circle(
+                center,
+                radius,
+                theme.selection_bg_fill.to(),
+                theme.selection_stroke.to(),
+            );
+            ui.painter().text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                badge.to_string(),
+                TextStyle::Small.resolve(ui.style()),
+                theme.noninteractive_fg_stroke.color().to(),
+            );
         }
 
-        if active && !ui.is_rect_visible(response.rect) {
+        if active && !ui.is_rect_visible(response.rect) && !response.hovered() {
             response.scroll_to_me(None)
         }
+
+        response
+    }
+}
+
+/// The responses to a query, kept sorted by descending priority as they arrive and capped at
+/// the best `limit`, so a flood of results costs neither memory nor sorting time. Responses
+/// from different engines with the same [`ResultItem::canonical_key`] share a row.
+#[derive(Debug)]
+pub struct ResultList {
+    responses: Vec<QueryResponse>,
+    limit: usize,
+    keys: HashMap<String, Uuid>,
+}
+
+impl ResultList {
+    pub fn new(limit: usize) -> Self {
+        ResultList {
+            responses: Vec::new(),
+            limit,
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        for response in self.responses.drain(limit.min(self.responses.len())..) {
+            if let Some(key) = &response.key
+                && self.keys.get(key) == Some(&response.uuid)
+            {
+                self.keys.remove(key);
+            }
+        }
+    }
+
+    /// Adds `response`, merging it into the row for the same result from another engine,
+    /// or else after any of equal priority unless it's outside the limit.
+    pub fn insert(&mut self, response: QueryResponse) {
+        let existing = response
+            .key
+            .as_ref()
+            .and_then(|key| self.keys.get(key))
+            .and_then(|uuid| self.responses.iter().position(|r| r.uuid == *uuid));
+
+        match existing {
+            // Several results from one engine, like matches on different lines of a file,
+            // stay apart.
+            Some(pos)
+                if !self.responses[pos]
+                    .engines
+                    .iter()
+                    .any(|engine| response.engines.contains(engine)) =>
+            {
+                let mut merged = self.responses.remove(pos);
+                merged.merge(response);
+                self.place(merged, false);
+            }
+            Some(_) => self.place(response, false),
+            None => self.place(response, true),
+        }
+    }
+
+    // Inserts `response` in order, remembering it as the row for its key if `keyed`.
+    fn place(&mut self, response: QueryResponse, keyed: bool) {
+        let pos = self
+            .responses
+            .partition_point(|other| other.priority >= response.priority);
+        if pos >= self.limit {
+            return;
+        }
+        if self.responses.len() >= self.limit
+            && let Some(dropped) = self.responses.pop()
+            && let Some(key) = &dropped.key
+            && self.keys.get(key) == Some(&dropped.uuid)
+        {
+            self.keys.remove(key);
+        }
+        if keyed && let Some(key) = &response.key {
+            self.keys.insert(key.clone(), response.uuid);
+        }
+        self.responses.insert(pos, response);
+    }
+
+    pub fn clear(&mut self) {
+        self.responses.clear();
+        self.keys.clear();
+    }
+}
+
+impl Extend<QueryResponse> for ResultList {
+    fn extend<I: IntoIterator<Item = QueryResponse>>(&mut self, responses: I) {
+        for response in responses {
+            self.insert(response);
+        }
+    }
+}
+
+impl std::ops::Deref for ResultList {
+    type Target = [QueryResponse];
+
+    fn deref(&self) -> &Self::Target {
+        &self.responses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Hit {
+        path: PathBuf,
+        line: u64,
+    }
+
+    #[test]
+    fn payloads_downcast_and_round_trip() {
+        let hit = Hit {
+            path: "src/main.rs".into(),
+            line: 42,
+        };
+        let response = QueryResponse::new(ResultItem::new(Icon::Glyph("".into()), "main"), 0)
+            .with_payload(Payload::new(hit.clone()));
+        assert_eq!(response.payload_as::<Hit>(), Some(&hit));
+        assert_eq!(response.payload_as::<String>(), None);
+
+        let persisted = response.payload().unwrap().persist().unwrap();
+        let json = serde_json::to_string(&persisted).unwrap();
+        let persisted: PersistedPayload = serde_json::from_str(&json).unwrap();
+        assert!(persisted.restore::<String>().is_none());
+        let restored = persisted.restore::<Hit>().unwrap();
+        assert_eq!(restored.downcast_ref::<Hit>(), Some(&hit));
+    }
+
+    // Notes the action or query, and the line of the hit, each time it's called.
+    #[cfg(feature = "gui")]
+    #[derive(Default)]
+    struct Recorder(parking_lot::Mutex<Vec<(String, u64)>>);
+
+    #[cfg(feature = "gui")]
+    #[async_trait::async_trait]
+    impl crate::query::SearchEngine for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        fn prefix(&self) -> &str {
+            "@rec"
+        }
+
+        fn description(&self) -> &str {
+            "Record what ran"
+        }
+
+        fn icon(&self) -> Icon {
+            Icon::Glyph("R".into())
+        }
+
+        fn run_action(
+            &self,
+            action: &str,
+            payload: Option<&Payload>,
+        ) -> anyhow::Result<Option<ActionKind>> {
+            let hit = payload
+                .and_then(Payload::downcast_ref::<Hit>)
+                .ok_or_else(|| anyhow::anyhow!("no hit"))?;
+            self.0.lock().push((action.to_string(), hit.line));
+            Ok(None)
+        }
+
+        fn record(&self, query: &str, payload: Option<&Payload>) {
+            if let Some(hit) = payload.and_then(Payload::downcast_ref::<Hit>) {
+                self.0.lock().push((query.to_string(), hit.line));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn engine_actions_and_history_get_the_payload() {
+        let recorder = Arc::new(Recorder::default());
+        let item = ResultItem::new(Icon::Glyph("".into()), "main")
+            .with_action("Jump", ActionKind::Engine("jump".into()));
+        let hit = Hit {
+            path: "src/main.rs".into(),
+            line: 42,
+        };
+        let mut response = QueryResponse::new(item, 0).with_payload(Payload::new(hit));
+        let ctx = Context::default();
+
+        // Nothing can run the action before the response is relayed from its engine.
+        assert!(!response.run(Command::Execute, &ctx));
+        response.source = Some(recorder.clone() as Engine);
+        assert!(response.run(Command::Execute, &ctx));
+        assert!(!response.run(Command::ExecuteAlt, &ctx));
+        response.record("@rec main");
+        assert_eq!(
+            *recorder.0.lock(),
+            [("jump".to_string(), 42), ("@rec main".to_string(), 42)]
+        );
+    }
+
+    fn response(title: &str, priority: i64) -> QueryResponse {
+        QueryResponse::new(ResultItem::new(Icon::Glyph("".into()), title), priority)
+    }
+
+    fn titles(list: &ResultList) -> Vec<&str> {
+        list.iter().map(|resp| resp.item.title.as_str()).collect()
+    }
+
+    #[test]
+    fn result_list_keeps_the_best_in_order() {
+        let mut list = ResultList::new(3);
+        list.extend([
+            response("a", 1),
+            response("b", 5),
+            response("c", 1),
+            response("d", 0),
+        ]);
+        assert_eq!(titles(&list), ["b", "a", "c"]);
+
+        list.insert(response("e", 3));
+        assert_eq!(titles(&list), ["b", "e", "a"]);
+        // Ties with the last kept result lose to it, since they arrived later.
+        list.insert(response("f", 1));
+        assert_eq!(titles(&list), ["b", "e", "a"]);
+
+        list.set_limit(2);
+        assert_eq!(titles(&list), ["b", "e"]);
+    }
+
+    fn found(engine: &str, icon: &str, path: &str, priority: i64) -> QueryResponse {
+        let item = ResultItem::new(Icon::Glyph(icon.into()), path)
+            .with_action("Copy path", ActionKind::Copy(path.into()))
+            .with_action("Open", ActionKind::OpenPath(path.into()));
+        let mut response = QueryResponse::new(item, priority).with_key();
+        response.engines.push(engine.into());
+        response
+    }
+
+    #[test]
+    fn result_list_merges_across_engines() {
+        let mut list = ResultList::new(10);
+        list.insert(found("fzf", "F", "/home/a.rs", 2));
+        list.insert(found("fzf", "F", "/home/b.rs", 1));
+        list.insert(found("rga", "R", "/home/./b.rs", 3).with_payload(Payload::new(7)));
+        // Another match in the same file from the same engine is a row of its own.
+        list.insert(found("rga", "R", "/home/b.rs", 0));
+
+        assert_eq!(list.len(), 3);
+        let merged = &list[0];
+        assert_eq!(merged.priority, 4);
+        assert_eq!(merged.engines, ["rga", "fzf"]);
+        assert_eq!(merged.item.icon, Icon::Glyph("R".into()));
+        assert_eq!(merged.item.sources, [Icon::Glyph("F".into())]);
+        assert_eq!(merged.payload_as::<i32>(), Some(&7));
+        assert_eq!(
+            merged.item.actions,
+            [
+                ResultAction::new("Copy path", ActionKind::Copy("/home/./b.rs".into())),
+                ResultAction::new("Open", ActionKind::OpenPath("/home/./b.rs".into())),
+            ]
+        );
+        assert_eq!(titles(&list), ["/home/./b.rs", "/home/a.rs", "/home/b.rs"]);
+    }
+
+    #[test]
+    fn canonical_keys_ignore_fragments_and_trailing_slashes() {
+        let page = |url: &str| {
+            ResultItem::new(Icon::Glyph("".into()), "")
+                .with_action("Open", ActionKind::OpenUrl(url.into()))
+        };
+        assert_eq!(
+            page("https://EN.wikipedia.org/wiki/Rust#History").canonical_key(),
+            page("https://en.wikipedia.org/wiki/Rust/").canonical_key()
+        );
+        assert_eq!(
+            ResultItem::new(Icon::Glyph("".into()), "")
+                .with_action("Copy", ActionKind::Copy("x".into()))
+                .canonical_key(),
+            None
+        );
+    }
+
+    #[test]
+    fn file_urls_are_encoded() {
+        assert_eq!(
+            file_url("/tmp/a b/#1?.rs").as_deref(),
+            Some("file:///tmp/a%20b/%231%3F.rs")
+        );
+        assert_eq!(file_url("relative.rs"), None);
+    }
+
+    #[test]
+    fn canonical_keys_resolve_paths() {
+        let file = |path: &str| {
+            ResultItem::new(Icon::Glyph("".into()), "")
+                .with_action("Open", ActionKind::OpenPath(path.into()))
+                .canonical_key()
+        };
+        assert_eq!(
+            file("/nonexistent/src/../lib.rs"),
+            file("/nonexistent/./lib.rs")
+        );
+
+        let cwd = std::env::current_dir().unwrap();
+        let dir = cwd.file_name().unwrap().to_str().unwrap();
+        assert_eq!(file("Cargo.toml"), file(&format!("../{dir}/Cargo.toml")));
+        assert_eq!(
+            file("Cargo.toml"),
+            file(cwd.join("Cargo.toml").to_str().unwrap())
+        );
     }
 }
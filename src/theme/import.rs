@@ -0,0 +1,415 @@
+use crate::config::expand_home;
+use crate::theme::{RgbaUnmultiplied, Stroke, Theme, ThemeError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Base16,
+    Pywal,
+    Alacritty,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yaml" | "yml" => Some(ImportFormat::Base16),
+            "json" => Some(ImportFormat::Pywal),
+            "toml" => Some(ImportFormat::Alacritty),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = ThemeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base16" => Ok(ImportFormat::Base16),
+            "pywal" => Ok(ImportFormat::Pywal),
+            "alacritty" => Ok(ImportFormat::Alacritty),
+            _ => Err(ThemeError::Import(format!("unknown import format `{s}`"))),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ThemeImport {
+    Path(PathBuf),
+    Spec {
+        path: PathBuf,
+        format: Option<ImportFormat>,
+    },
+}
+
+impl ThemeImport {
+    pub fn path(&self) -> &Path {
+        match self {
+            ThemeImport::Path(path) | ThemeImport::Spec { path, .. } => path,
+        }
+    }
+
+    pub fn format(&self) -> Option<ImportFormat> {
+        match self {
+            ThemeImport::Path(path) => ImportFormat::from_path(path),
+            ThemeImport::Spec { path, format } => format.or_else(|| ImportFormat::from_path(path)),
+        }
+    }
+
+    pub fn load(&self) -> Result<Theme, ThemeError> {
+        let path = expand_home(self.path());
+        let format = self.format().ok_or_else(|| {
+            ThemeError::Import(format!("cannot infer the color scheme format of {path:?}"))
+        })?;
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| ThemeError::Io(path.to_string_lossy().to_string(), e))?;
+
+        Ok(Palette::parse(&source, format)?.to_theme())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Palette {
+    pub background: RgbaUnmultiplied,
+    pub surface: RgbaUnmultiplied,
+    pub selection: RgbaUnmultiplied,
+    pub muted: RgbaUnmultiplied,
+    pub foreground: RgbaUnmultiplied,
+    pub red: RgbaUnmultiplied,
+    pub orange: RgbaUnmultiplied,
+    pub yellow: RgbaUnmultiplied,
+    pub green: RgbaUnmultiplied,
+    pub cyan: RgbaUnmultiplied,
+    pub blue: RgbaUnmultiplied,
+    pub magenta: RgbaUnmultiplied,
+}
+
+fn parse_hex(value: &str) -> Result<RgbaUnmultiplied, ThemeError> {
+    let hex = value.trim().trim_matches('"').trim_matches('\'');
+    let hex = hex
+        .strip_prefix('#')
+        .or_else(|| hex.strip_prefix("0x"))
+        .unwrap_or(hex);
+
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| ThemeError::Import(format!("invalid color `{value}`")))
+    };
+
+    match hex.len() {
+        6 => Ok(RgbaUnmultiplied::rgb(channel(0)?, channel(2)?, channel(4)?)),
+        8 => Ok(RgbaUnmultiplied::List([
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            channel(6)?,
+        ])),
+        _ => Err(ThemeError::Import(format!("invalid color `{value}`"))),
+    }
+}
+
+fn mix(a: RgbaUnmultiplied, b: RgbaUnmultiplied, t: f32) -> RgbaUnmultiplied {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    let ([ar, ag, ab, _], [br, bg, bb, _]) = (a.rgba(), b.rgba());
+    RgbaUnmultiplied::rgb(lerp(ar, br), lerp(ag, bg), lerp(ab, bb))
+}
+
+#[derive(Debug, Deserialize)]
+struct PywalColors {
+    special: HashMap<String, String>,
+    colors: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlacrittyConfig {
+    colors: AlacrittyColors,
+}
+
+#[derive(Debug, Deserialize)]
+struct AlacrittyColors {
+    primary: HashMap<String, String>,
+    normal: HashMap<String, String>,
+    #[serde(default)]
+    bright: HashMap<String, String>,
+    #[serde(default)]
+    selection: HashMap<String, String>,
+}
+
+impl Palette {
+    pub fn parse(source: &str, format: ImportFormat) -> Result<Self, ThemeError> {
+        match format {
+            ImportFormat::Base16 => Self::from_base16(source),
+            ImportFormat::Pywal => Self::from_pywal(source),
+            ImportFormat::Alacritty => Self::from_alacritty(source),
+        }
+    }
+
+    // base16 schemes are flat `baseXX: "rrggbb"` maps, optionally nested under `palette:`
+    pub fn from_base16(source: &str) -> Result<Self, ThemeError> {
+        let colors: HashMap<String, String> = source
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| {
+                let value = value.split(" #").next().unwrap_or_default();
+                (key.trim().to_ascii_lowercase(), value.trim().to_string())
+            })
+            .filter(|(key, value)| key.starts_with("base") && !value.is_empty())
+            .collect();
+
+        let get = |key: &str| {
+            colors
+                .get(key)
+                .ok_or_else(|| ThemeError::Import(format!("base16 scheme is missing `{key}`")))
+                .and_then(|value| parse_hex(value))
+        };
+
+        Ok(Palette {
+            background: get("base00")?,
+            surface: get("base01")?,
+            selection: get("base02")?,
+            muted: get("base03")?,
+            foreground: get("base05")?,
+            red: get("base08")?,
+            orange: get("base09")?,
+            yellow: get("base0a")?,
+            green: get("base0b")?,
+            cyan: get("base0c")?,
+            blue: get("base0d")?,
+            magenta: get("base0e")?,
+        })
+    }
+
+    pub fn from_pywal(source: &str) -> Result<Self, ThemeError> {
+        let wal: PywalColors = serde_json::from_str(source)
+            .map_err(|e| ThemeError::Import(format!("invalid pywal colors: {e}")))?;
+
+        let special = |key: &str| {
+            wal.special
+                .get(key)
+                .ok_or_else(|| ThemeError::Import(format!("pywal colors are missing `{key}`")))
+                .and_then(|value| parse_hex(value))
+        };
+        let color = |i: u8| {
+            wal.colors
+                .get(&format!("color{i}"))
+                .ok_or_else(|| ThemeError::Import(format!("pywal colors are missing `color{i}`")))
+                .and_then(|value| parse_hex(value))
+        };
+
+        let background = special("background")?;
+        let foreground = special("foreground")?;
+
+        Ok(Palette {
+            background,
+            surface: mix(background, foreground, 0.1),
+            selection: mix(background, foreground, 0.2),
+            muted: color(8)?,
+            foreground,
+            red: color(1)?,
+            // pywal has no orange slot, so it's derived to keep warnings apart from yellow.
+            orange: mix(color(1)?, color(3)?, 0.5),
+            yellow: color(3)?,
+            green: color(2)?,
+            cyan: color(6)?,
+            blue: color(4)?,
+            magenta: color(5)?,
+        })
+    }
+
+    pub fn from_alacritty(source: &str) -> Result<Self, ThemeError> {
+        let config: AlacrittyConfig = toml::from_str(source)
+            .map_err(|e| ThemeError::Import(format!("invalid alacritty colors: {e}")))?;
+        let colors = config.colors;
+
+        let get = |table: &HashMap<String, String>, section: &str, key: &str| {
+            table
+                .get(key)
+                .ok_or_else(|| {
+                    ThemeError::Import(format!("alacritty colors are missing `{section}.{key}`"))
+                })
+                .and_then(|value| parse_hex(value))
+        };
+
+        let background = get(&colors.primary, "primary", "background")?;
+        let foreground = get(&colors.primary, "primary", "foreground")?;
+
+        Ok(Palette {
+            background,
+            surface: mix(background, foreground, 0.1),
+            selection: get(&colors.selection, "selection", "background")
+                .unwrap_or_else(|_| mix(background, foreground, 0.2)),
+            muted: get(&colors.bright, "bright", "black")
+                .unwrap_or_else(|_| mix(background, foreground, 0.4)),
+            foreground,
+            red: get(&colors.normal, "normal", "red")?,
+            orange: get(&colors.bright, "bright", "red")
+                .or_else(|_| get(&colors.normal, "normal", "yellow"))?,
+            yellow: get(&colors.normal, "normal", "yellow")?,
+            green: get(&colors.normal, "normal", "green")?,
+            cyan: get(&colors.normal, "normal", "cyan")?,
+            blue: get(&colors.normal, "normal", "blue")?,
+            magenta: get(&colors.normal, "normal", "magenta")?,
+        })
+    }
+
+    pub fn is_dark(&self) -> bool {
+        let [r, g, b, _] = self.background.rgba();
+        (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) < 128.
+    }
+
+    pub fn to_theme(self) -> Theme {
+        let background = self.background;
+        let surface = self.surface;
+        let hovered = mix(self.surface, self.selection, 0.5);
+        let selection = self.selection;
+        let muted = self.muted;
+        let foreground = self.foreground;
+
+        Theme {
+            dark_mode: self.is_dark(),
+            hyperlink_color: self.blue,
+            faint_bg_color: surface,
+            extreme_bg_color: mix(self.background, RgbaUnmultiplied::BLACK, 0.3),
+            code_bg_color: surface,
+            warn_fg_color: self.orange,
+            error_fg_color: self.red,
+            window_fill: self.background.with_alpha(230),
+            panel_fill: background,
+            window_stroke: Stroke(0.0, muted),
+            noninteractive_bg_fill: background,
+            noninteractive_weak_bg_fill: background,
+            noninteractive_bg_stroke: Stroke(0.0, muted),
+            noninteractive_fg_stroke: Stroke(1.0, foreground),
+            inactive_bg_fill: surface,
+            inactive_weak_bg_fill: surface,
+            inactive_bg_stroke: Stroke::NONE,
+            inactive_fg_stroke: Stroke(1.0, foreground),
+            hovered_bg_fill: hovered,
+            hovered_weak_bg_fill: hovered,
+            hovered_bg_stroke: Stroke(1.0, muted),
+            hovered_fg_stroke: Stroke(1.0, foreground),
+            active_bg_fill: selection,
+            active_weak_bg_fill: selection,
+            active_bg_stroke: Stroke(1.0, muted),
+            active_fg_stroke: Stroke(1.0, foreground),
+            open_bg_fill: surface,
+            open_weak_bg_fill: surface,
+            open_bg_stroke: Stroke(1.0, muted),
+            open_fg_stroke: Stroke(1.0, foreground),
+            selection_bg_fill: self.blue.with_alpha(51),
+            selection_stroke: Stroke(1.0, foreground),
+            selected_bg_fill: self.magenta.with_alpha(64),
+            ..Theme::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE16: &str = r#"
+scheme: "Test"
+author: "Amoeba"
+palette:
+  base00: "1d1f21" # background
+  base01: "282a2e"
+  base02: "373b41"
+  base03: "969896"
+  base04: "b4b7b4"
+  base05: "c5c8c6"
+  base06: "e0e0e0"
+  base07: "ffffff"
+  base08: "cc6666"
+  base09: "de935f"
+  base0A: "f0c674"
+  base0B: "b5bd68"
+  base0C: "8abeb7"
+  base0D: "81a2be"
+  base0E: "b294bb"
+  base0F: "a3685a"
+"#;
+
+    const PYWAL: &str = r##"{
+  "special": { "background": "#101010", "foreground": "#f0f0f0", "cursor": "#f0f0f0" },
+  "colors": {
+    "color0": "#101010", "color1": "#c00000", "color2": "#00c000", "color3": "#c0c000",
+    "color4": "#0000c0", "color5": "#c000c0", "color6": "#00c0c0", "color7": "#c0c0c0",
+    "color8": "#808080", "color9": "#ff0000", "color10": "#00ff00", "color11": "#ffff00",
+    "color12": "#0000ff", "color13": "#ff00ff", "color14": "#00ffff", "color15": "#ffffff"
+  }
+}"##;
+
+    const ALACRITTY: &str = r##"
+[colors.primary]
+background = "#eff1f5"
+foreground = "#4c4f69"
+
+[colors.normal]
+black = "#5c5f77"
+red = "#d20f39"
+green = "#40a02b"
+yellow = "#df8e1d"
+blue = "#1e66f5"
+magenta = "#ea76cb"
+cyan = "#179299"
+white = "#acb0be"
+
+[colors.bright]
+black = "#6c6f85"
+red = "#fe640b"
+"##;
+
+    fn hex(value: &str) -> RgbaUnmultiplied {
+        parse_hex(value).unwrap()
+    }
+
+    #[test]
+    fn imports_base16() {
+        let palette = Palette::parse(BASE16, ImportFormat::Base16).unwrap();
+        assert_eq!(palette.background, hex("1d1f21"));
+        assert_eq!(palette.foreground, hex("c5c8c6"));
+        assert_eq!(palette.orange, hex("de935f"));
+        assert_eq!(palette.yellow, hex("f0c674"));
+        assert!(palette.is_dark());
+
+        let missing = BASE16.replace("base0D", "baseXX");
+        assert!(Palette::parse(&missing, ImportFormat::Base16).is_err());
+    }
+
+    #[test]
+    fn imports_pywal() {
+        let palette = Palette::parse(PYWAL, ImportFormat::Pywal).unwrap();
+        assert_eq!(palette.background, hex("101010"));
+        assert_eq!(palette.red, hex("c00000"));
+        assert_eq!(palette.yellow, hex("c0c000"));
+        assert_eq!(palette.muted, hex("808080"));
+        assert_ne!(palette.orange, palette.yellow);
+        assert_ne!(palette.orange, palette.red);
+
+        let theme = palette.to_theme();
+        assert!(theme.dark_mode);
+        assert_ne!(theme.warn_fg_color, theme.error_fg_color);
+    }
+
+    #[test]
+    fn imports_alacritty() {
+        let palette = Palette::parse(ALACRITTY, ImportFormat::Alacritty).unwrap();
+        assert_eq!(palette.background, hex("eff1f5"));
+        assert_eq!(palette.orange, hex("fe640b"));
+        assert_eq!(palette.muted, hex("6c6f85"));
+        assert_eq!(
+            palette.selection,
+            mix(hex("eff1f5"), hex("4c4f69"), 0.2),
+            "selection falls back to a mix"
+        );
+        assert!(!palette.is_dark());
+
+        assert!(Palette::parse("[colors]", ImportFormat::Alacritty).is_err());
+    }
+}
@@ -0,0 +1,568 @@
+use crate::config::expand_home;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+mod import;
+#[cfg(feature = "gui")]
+mod style;
+
+pub use import::{ImportFormat, Palette, ThemeImport};
+#[cfg(feature = "gui")]
+pub use style::{Color32Ext, CornerRadiusExt};
+
+pub const DEFAULT_THEME: &str = "dark";
+
+pub const BUNDLED_THEMES: &[(&str, &str)] = &[
+    ("light", include_str!("../../themes/light.toml")),
+    (
+        "high-contrast",
+        include_str!("../../themes/high-contrast.toml"),
+    ),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub font_size: f32,
+    pub margin: Margin,
+    pub query_corner_radius: CornerRadius,
+    pub query_corner_radius_with_results: CornerRadius,
+    pub max_height: f32,
+
+    // egui Visual
+    pub dark_mode: bool,
+    pub hyperlink_color: RgbaUnmultiplied,
+    pub faint_bg_color: RgbaUnmultiplied,
+    pub extreme_bg_color: RgbaUnmultiplied,
+    pub code_bg_color: RgbaUnmultiplied,
+    pub warn_fg_color: RgbaUnmultiplied,
+    pub error_fg_color: RgbaUnmultiplied,
+    pub window_fill: RgbaUnmultiplied,
+    pub panel_fill: RgbaUnmultiplied,
+    pub window_stroke: Stroke,
+    pub noninteractive_bg_fill: RgbaUnmultiplied,
+    pub noninteractive_weak_bg_fill: RgbaUnmultiplied,
+    pub noninteractive_bg_stroke: Stroke,
+    pub noninteractive_fg_stroke: Stroke,
+    pub noninteractive_corner_radius: CornerRadius,
+    pub noninteractive_expansion: f32,
+    pub inactive_bg_fill: RgbaUnmultiplied,
+    pub inactive_weak_bg_fill: RgbaUnmultiplied,
+    pub inactive_bg_stroke: Stroke,
+    pub inactive_fg_stroke: Stroke,
+    pub inactive_corner_radius: CornerRadius,
+    pub inactive_expansion: f32,
+    pub hovered_bg_fill: RgbaUnmultiplied,
+    pub hovered_weak_bg_fill: RgbaUnmultiplied,
+    pub hovered_bg_stroke: Stroke,
+    pub hovered_fg_stroke: Stroke,
+    pub hovered_corner_radius: CornerRadius,
+    pub hovered_expansion: f32,
+    pub active_bg_fill: RgbaUnmultiplied,
+    pub active_weak_bg_fill: RgbaUnmultiplied,
+    pub active_bg_stroke: Stroke,
+    pub active_fg_stroke: Stroke,
+    pub active_corner_radius: CornerRadius,
+    pub active_expansion: f32,
+    pub open_bg_fill: RgbaUnmultiplied,
+    pub open_weak_bg_fill: RgbaUnmultiplied,
+    pub open_bg_stroke: Stroke,
+    pub open_fg_stroke: Stroke,
+    pub open_corner_radius: CornerRadius,
+    pub open_expansion: f32,
+    pub selection_bg_fill: RgbaUnmultiplied,
+    pub selection_stroke: Stroke,
+    pub selected_bg_fill: RgbaUnmultiplied,
+    pub window_shadow_color: RgbaUnmultiplied,
+    pub window_shadow_offset: [i8; 2],
+    pub window_shadow_blur: u8,
+    pub window_shadow_spread: u8,
+    pub popup_shadow_color: RgbaUnmultiplied,
+    pub popup_shadow_offset: [i8; 2],
+    pub popup_shadow_blur: u8,
+    pub popup_shadow_spread: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub struct Margin {
+    pub left: i8,
+    pub right: i8,
+    pub top: i8,
+    pub bottom: i8,
+}
+
+impl Margin {
+    pub const NONE: Margin = Margin::splat(0);
+
+    pub const fn symmetric(x: i8, y: i8) -> Margin {
+        Margin {
+            left: x,
+            right: y,
+            top: y,
+            bottom: y,
+        }
+    }
+    pub const fn splat(v: i8) -> Margin {
+        Margin {
+            left: v,
+            right: v,
+            top: v,
+            bottom: v,
+        }
+    }
+
+    pub const fn x(self) -> i8 {
+        self.left + self.right
+    }
+
+    pub const fn y(self) -> i8 {
+        self.top + self.bottom
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub struct CornerRadius {
+    pub ne: f32,
+    pub nw: f32,
+    pub se: f32,
+    pub sw: f32,
+}
+
+impl CornerRadius {
+    pub const NONE: CornerRadius = CornerRadius::splat(0.);
+
+    const fn splat(v: f32) -> Self {
+        CornerRadius {
+            ne: v,
+            nw: v,
+            se: v,
+            sw: v,
+        }
+    }
+
+    pub const fn flip_y(self) -> Self {
+        Self {
+            ne: self.se,
+            nw: self.sw,
+            se: self.ne,
+            sw: self.nw,
+        }
+    }
+
+    pub const fn flip_x(self) -> Self {
+        Self {
+            ne: self.nw,
+            nw: self.ne,
+            se: self.sw,
+            sw: self.se,
+        }
+    }
+}
+
+impl Default for CornerRadius {
+    fn default() -> Self {
+        CornerRadius::splat(0.)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub struct Stroke(f32, RgbaUnmultiplied);
+
+impl Stroke {
+    pub const NONE: Self = Stroke(0., RgbaUnmultiplied::TRANSPARENT);
+
+    pub fn thickness(self) -> f32 {
+        self.0
+    }
+
+    pub fn color(self) -> RgbaUnmultiplied {
+        self.1
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+#[serde(untagged)]
+pub enum RgbaUnmultiplied {
+    Struct { r: u8, g: u8, b: u8, a: u8 },
+    Tuple(u8, u8, u8, u8),
+    List([u8; 4]),
+}
+
+impl RgbaUnmultiplied {
+    pub const TRANSPARENT: Self = RgbaUnmultiplied::Struct {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0,
+    };
+    pub const BLACK: Self = RgbaUnmultiplied::Struct {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+    pub const RED: Self = RgbaUnmultiplied::Struct {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+    pub const GREEN: Self = RgbaUnmultiplied::Struct {
+        r: 0,
+        g: 255,
+        b: 0,
+        a: 255,
+    };
+    pub const BLUE: Self = RgbaUnmultiplied::Struct {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        RgbaUnmultiplied::List([r, g, b, 255])
+    }
+
+    pub const fn rgba(self) -> [u8; 4] {
+        match self {
+            RgbaUnmultiplied::Struct { r, g, b, a } => [r, g, b, a],
+            RgbaUnmultiplied::Tuple(r, g, b, a) => [r, g, b, a],
+            RgbaUnmultiplied::List(rgba) => rgba,
+        }
+    }
+
+    pub const fn with_alpha(self, a: u8) -> Self {
+        let [r, g, b, _] = self.rgba();
+        RgbaUnmultiplied::List([r, g, b, a])
+    }
+}
+
+// The same color written in different forms is equal.
+impl PartialEq for RgbaUnmultiplied {
+    fn eq(&self, other: &Self) -> bool {
+        self.rgba() == other.rgba()
+    }
+}
+
+impl Eq for RgbaUnmultiplied {}
+
+impl Default for Theme {
+    fn default() -> Self {
+        #![allow(unused_variables)]
+
+        let rosewater = RgbaUnmultiplied::rgb(245, 224, 220);
+        let flamingo = RgbaUnmultiplied::rgb(242, 205, 205);
+        let pink = RgbaUnmultiplied::rgb(245, 194, 231);
+        let mauve = RgbaUnmultiplied::rgb(203, 166, 247);
+        let red = RgbaUnmultiplied::rgb(243, 139, 168);
+        let maroon = RgbaUnmultiplied::rgb(235, 160, 172);
+        let peach = RgbaUnmultiplied::rgb(250, 179, 135);
+        let yellow = RgbaUnmultiplied::rgb(249, 226, 175);
+        let green = RgbaUnmultiplied::rgb(166, 227, 161);
+        let teal = RgbaUnmultiplied::rgb(148, 226, 213);
+        let sky = RgbaUnmultiplied::rgb(137, 220, 235);
+        let sapphire = RgbaUnmultiplied::rgb(116, 199, 236);
+        let blue = RgbaUnmultiplied::rgb(137, 180, 250);
+        let lavender = RgbaUnmultiplied::rgb(180, 190, 254);
+        let text = RgbaUnmultiplied::rgb(205, 214, 244);
+        let subtext1 = RgbaUnmultiplied::rgb(186, 194, 222);
+        let subtext0 = RgbaUnmultiplied::rgb(166, 173, 200);
+        let overlay2 = RgbaUnmultiplied::rgb(147, 153, 178);
+        let overlay1 = RgbaUnmultiplied::rgb(127, 132, 156);
+        let overlay0 = RgbaUnmultiplied::rgb(108, 112, 134);
+        let surface2 = RgbaUnmultiplied::rgb(88, 91, 112);
+        let surface1 = RgbaUnmultiplied::rgb(69, 71, 90);
+        let surface0 = RgbaUnmultiplied::rgb(49, 50, 68);
+        let base = RgbaUnmultiplied::rgb(30, 30, 46);
+        let mantle = RgbaUnmultiplied::rgb(24, 24, 37);
+        let crust = RgbaUnmultiplied::rgb(17, 17, 27);
+
+        Theme {
+            font_size: 16.0,
+            margin: Margin::symmetric(20, 10),
+            query_corner_radius: CornerRadius::splat(20.),
+            query_corner_radius_with_results: CornerRadius {
+                ne: 20.,
+                nw: 20.,
+                se: 0.,
+                sw: 0.,
+            },
+            max_height: 500.0,
+
+            // egui Visual
+            dark_mode: true,
+            hyperlink_color: rosewater,
+            faint_bg_color: surface0,
+            extreme_bg_color: crust,
+            code_bg_color: mantle,
+            warn_fg_color: peach,
+            error_fg_color: maroon,
+            window_fill: base.with_alpha(230),
+            panel_fill: base,
+            window_stroke: Stroke(0.0, overlay1),
+            noninteractive_bg_fill: base,
+            noninteractive_weak_bg_fill: base,
+            noninteractive_bg_stroke: Stroke(0.0, overlay1),
+            noninteractive_fg_stroke: Stroke(1.0, text),
+            noninteractive_corner_radius: CornerRadius::splat(2.),
+            noninteractive_expansion: 0.0,
+            inactive_bg_fill: surface0,
+            inactive_weak_bg_fill: surface0,
+            inactive_bg_stroke: Stroke::NONE,
+            inactive_fg_stroke: Stroke(1.0, text),
+            inactive_corner_radius: CornerRadius::splat(2.),
+            inactive_expansion: 0.0,
+            hovered_bg_fill: surface2,
+            hovered_weak_bg_fill: surface2,
+            hovered_bg_stroke: Stroke(1.0, overlay1),
+            hovered_fg_stroke: Stroke(1.0, text),
+            hovered_corner_radius: CornerRadius::splat(3.),
+            hovered_expansion: 0.0,
+            active_bg_fill: surface1,
+            active_weak_bg_fill: surface1,
+            active_bg_stroke: Stroke(1.0, overlay1),
+            active_fg_stroke: Stroke(1.0, text),
+            active_corner_radius: CornerRadius::splat(2.),
+            active_expansion: 0.0,
+            open_bg_fill: surface0,
+            open_weak_bg_fill: surface0,
+            open_bg_stroke: Stroke(1.0, overlay1),
+            open_fg_stroke: Stroke(1.0, text),
+            open_corner_radius: CornerRadius::splat(2.),
+            open_expansion: 0.0,
+            selection_bg_fill: blue.with_alpha(51),
+            selection_stroke: Stroke(1.0, text),
+            selected_bg_fill: mauve.with_alpha(64),
+            window_shadow_color: RgbaUnmultiplied::BLACK.with_alpha(96),
+            window_shadow_offset: [10, 20],
+            window_shadow_blur: 15,
+            window_shadow_spread: 0,
+            popup_shadow_color: RgbaUnmultiplied::BLACK.with_alpha(96),
+            popup_shadow_offset: [6, 10],
+            popup_shadow_blur: 8,
+            popup_shadow_spread: 0,
+        }
+    }
+}
+
+impl Theme {
+    pub fn light() -> Self {
+        ThemeLoader::default()
+            .load("light")
+            .expect("bundled light theme is valid")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThemeError {
+    #[error("unknown theme `{0}`")]
+    Unknown(String),
+    #[error("theme inheritance cycle: {0}")]
+    Cycle(String),
+    #[error("invalid theme `{0}`: {1}")]
+    Invalid(String, String),
+    #[error("failed to import color scheme: {0}")]
+    Import(String),
+    #[error("failed to read theme `{0}`: {1}")]
+    Io(String, std::io::Error),
+    #[error("failed to parse theme `{0}`: {1}")]
+    Parse(String, toml::de::Error),
+    #[error(transparent)]
+    Serialize(#[from] toml::ser::Error),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ThemeLoader {
+    dir: Option<PathBuf>,
+    sources: RefCell<Vec<PathBuf>>,
+}
+
+impl ThemeLoader {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            sources: RefCell::default(),
+        }
+    }
+
+    // The user theme files and imported color schemes read so far.
+    pub fn sources(&self) -> Vec<PathBuf> {
+        self.sources.borrow().clone()
+    }
+
+    fn read(&self, name: &str, path: PathBuf) -> Result<String, ThemeError> {
+        let source =
+            std::fs::read_to_string(&path).map_err(|e| ThemeError::Io(name.to_string(), e));
+        self.sources.borrow_mut().push(path);
+        source
+    }
+
+    pub fn load(&self, name: &str) -> Result<Theme, ThemeError> {
+        self.resolve(toml::Value::String(name.to_string()))
+    }
+
+    pub fn resolve(&self, value: toml::Value) -> Result<Theme, ThemeError> {
+        let mut visited = Vec::new();
+        let (name, table) = match value {
+            toml::Value::String(name) => {
+                let table = self.named(&name, &mut visited)?;
+                (name, table)
+            }
+            toml::Value::Table(table) => ("theme".to_string(), self.extend(table, &mut visited)?),
+            other => {
+                return Err(ThemeError::Invalid(
+                    "theme".to_string(),
+                    format!("expected a theme name or table, found {}", other.type_str()),
+                ));
+            }
+        };
+
+        table.try_into().map_err(|e| ThemeError::Parse(name, e))
+    }
+
+    fn named(&self, name: &str, visited: &mut Vec<String>) -> Result<toml::Table, ThemeError> {
+        if visited.iter().any(|v| v == name) {
+            visited.push(name.to_string());
+            return Err(ThemeError::Cycle(visited.join(" -> ")));
+        }
+        visited.push(name.to_string());
+
+        let user_path = self
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{name}.toml")))
+            .filter(|path| path.is_file());
+
+        let source = if let Some(path) = user_path {
+            self.read(name, path)?
+        } else if name == DEFAULT_THEME {
+            return Ok(toml::Table::try_from(Theme::default())?);
+        } else if let Some((_, source)) = BUNDLED_THEMES.iter().find(|(n, _)| *n == name) {
+            source.to_string()
+        } else {
+            return Err(ThemeError::Unknown(name.to_string()));
+        };
+
+        let table = toml::from_str(&source).map_err(|e| ThemeError::Parse(name.to_string(), e))?;
+        self.extend(table, visited)
+    }
+
+    fn extend(
+        &self,
+        mut table: toml::Table,
+        visited: &mut Vec<String>,
+    ) -> Result<toml::Table, ThemeError> {
+        let name = || {
+            visited
+                .last()
+                .cloned()
+                .unwrap_or_else(|| "theme".to_string())
+        };
+
+        let mut base = match (table.remove("extends"), table.remove("import")) {
+            (None, None) => toml::Table::try_from(Theme::default())?,
+            (None, Some(import)) => {
+                let import: ThemeImport = import
+                    .try_into()
+                    .map_err(|e: toml::de::Error| ThemeError::Parse(name(), e))?;
+                self.sources.borrow_mut().push(expand_home(import.path()));
+                toml::Table::try_from(import.load()?)?
+            }
+            (Some(toml::Value::String(base)), None) => self.named(&base, visited)?,
+            (Some(_), Some(_)) => {
+                return Err(ThemeError::Invalid(
+                    name(),
+                    "`extends` and `import` are mutually exclusive".to_string(),
+                ));
+            }
+            (Some(other), None) => {
+                return Err(ThemeError::Invalid(
+                    name(),
+                    format!("`extends` must be a theme name, found {}", other.type_str()),
+                ));
+            }
+        };
+
+        merge(&mut base, table);
+        Ok(base)
+    }
+}
+
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A themes directory holding `files`, as (name, contents).
+    fn themes(files: &[(&str, &str)]) -> ThemeLoader {
+        let dir = std::env::temp_dir().join(format!("amoeba-themes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.join(format!("{name}.toml")), contents).unwrap();
+        }
+        ThemeLoader::new(Some(dir))
+    }
+
+    #[test]
+    fn reports_inheritance_cycles() {
+        let loader = themes(&[("a", "extends = \"b\""), ("b", "extends = \"a\"")]);
+        match loader.load("a") {
+            Err(ThemeError::Cycle(path)) => assert_eq!(path, "a -> b -> a"),
+            other => panic!("expected a cycle, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn child_overrides_parent() {
+        let loader = themes(&[
+            (
+                "parent",
+                "font_size = 20.0\nmax_height = 300.0\nmargin = { left = 1, right = 2, top = 3, bottom = 4 }",
+            ),
+            (
+                "child",
+                "extends = \"parent\"\nfont_size = 30.0\nmargin = { left = 9 }",
+            ),
+        ]);
+        let theme = loader.load("child").unwrap();
+        assert_eq!(theme.font_size, 30.);
+        assert_eq!(theme.max_height, 300.);
+        assert_eq!(
+            (
+                theme.margin.left,
+                theme.margin.right,
+                theme.margin.top,
+                theme.margin.bottom
+            ),
+            (9, 2, 3, 4)
+        );
+    }
+
+    #[test]
+    fn partial_theme_applies_over_default() {
+        let loader = ThemeLoader::default();
+        let partial: toml::Value = toml::from_str("font_size = 12.0").unwrap();
+        let theme = loader.resolve(partial).unwrap();
+        let default = Theme::default();
+        assert_eq!(theme.font_size, 12.);
+        assert_eq!(theme.max_height, default.max_height);
+        assert_eq!(theme.window_fill, default.window_fill);
+
+        let light = loader.load("light").unwrap();
+        assert!(!light.dark_mode);
+        assert_eq!(light.font_size, default.font_size);
+        assert!(matches!(loader.load("nope"), Err(ThemeError::Unknown(_))));
+    }
+}
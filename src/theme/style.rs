@@ -0,0 +1,206 @@
+#![allow(clippy::from_over_into)]
+
+use super::{CornerRadius, Margin, RgbaUnmultiplied, Stroke, Theme};
+use egui::style::{Selection, WidgetVisuals, Widgets};
+use egui::{Color32, Shadow};
+
+impl Margin {
+    pub const fn to(self) -> egui::Margin {
+        let Self {
+            left,
+            right,
+            top,
+            bottom,
+        } = self;
+
+        egui::Margin {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+}
+
+impl Into<egui::Margin> for Margin {
+    fn into(self) -> egui::Margin {
+        self.to()
+    }
+}
+
+impl CornerRadius {
+    pub fn to(self) -> egui::CornerRadius {
+        let CornerRadius { ne, nw, se, sw } = self;
+        egui::epaint::CornerRadiusF32 { ne, nw, se, sw }.into()
+    }
+}
+
+impl Into<egui::CornerRadius> for CornerRadius {
+    fn into(self) -> egui::CornerRadius {
+        self.to()
+    }
+}
+
+pub trait CornerRadiusExt {
+    fn to(&self) -> CornerRadius;
+}
+
+impl CornerRadiusExt for egui::epaint::CornerRadiusF32 {
+    fn to(&self) -> CornerRadius {
+        CornerRadius {
+            ne: self.ne,
+            nw: self.nw,
+            se: self.se,
+            sw: self.sw,
+        }
+    }
+}
+
+impl CornerRadiusExt for egui::CornerRadius {
+    fn to(&self) -> CornerRadius {
+        egui::epaint::CornerRadiusF32::from(*self).to()
+    }
+}
+
+impl Stroke {
+    pub fn to(self) -> egui::Stroke {
+        egui::Stroke {
+            width: self.0,
+            color: self.1.to(),
+        }
+    }
+}
+
+impl Into<egui::Stroke> for Stroke {
+    fn into(self) -> egui::Stroke {
+        self.to()
+    }
+}
+
+impl RgbaUnmultiplied {
+    pub fn to(self) -> Color32 {
+        let [r, g, b, a] = self.rgba();
+        Color32::from_rgba_unmultiplied(r, g, b, a)
+    }
+}
+
+impl Into<Color32> for RgbaUnmultiplied {
+    fn into(self) -> Color32 {
+        self.to()
+    }
+}
+
+pub trait Color32Ext {
+    fn to(&self) -> RgbaUnmultiplied;
+}
+
+impl Color32Ext for Color32 {
+    fn to(&self) -> RgbaUnmultiplied {
+        RgbaUnmultiplied::List(self.to_srgba_unmultiplied())
+    }
+}
+
+impl Theme {
+    // Frame of a row in the result area; the last row takes the bottom corners of the window.
+    pub fn row_frame(&self, fill: RgbaUnmultiplied, last: bool) -> egui::Frame {
+        egui::Frame::NONE
+            .fill(fill.to())
+            .stroke(egui::Stroke::NONE)
+            .shadow(egui::Shadow::NONE)
+            .inner_margin(self.margin)
+            .outer_margin(Margin::symmetric(0, -2))
+            .corner_radius(
+                if last {
+                    self.query_corner_radius_with_results.flip_y()
+                } else {
+                    CornerRadius::NONE
+                }
+                .to(),
+            )
+    }
+
+    pub fn update(&self, style: &mut egui::Style) {
+        style.text_styles.insert(
+            egui::TextStyle::Body,
+            egui::FontId::proportional(self.font_size),
+        );
+
+        style.text_styles.insert(
+            egui::TextStyle::Monospace,
+            egui::FontId::monospace(self.font_size),
+        );
+
+        let old_visual = style.visuals.clone();
+        style.visuals = egui::Visuals {
+            dark_mode: self.dark_mode,
+            hyperlink_color: self.hyperlink_color.to(),
+            faint_bg_color: self.faint_bg_color.to(),
+            extreme_bg_color: self.extreme_bg_color.to(),
+            code_bg_color: self.code_bg_color.to(),
+            warn_fg_color: self.warn_fg_color.to(),
+            error_fg_color: self.error_fg_color.to(),
+            window_fill: self.window_fill.to(),
+            panel_fill: self.panel_fill.to(),
+            window_stroke: self.window_stroke.to(),
+            widgets: Widgets {
+                noninteractive: WidgetVisuals {
+                    bg_fill: self.noninteractive_bg_fill.to(),
+                    weak_bg_fill: self.noninteractive_weak_bg_fill.to(),
+                    bg_stroke: self.noninteractive_bg_stroke.to(),
+                    fg_stroke: self.noninteractive_fg_stroke.to(),
+                    corner_radius: self.noninteractive_corner_radius.to(),
+                    expansion: self.noninteractive_expansion,
+                },
+                inactive: WidgetVisuals {
+                    bg_fill: self.inactive_bg_fill.to(),
+                    weak_bg_fill: self.inactive_weak_bg_fill.to(),
+                    bg_stroke: self.inactive_bg_stroke.to(),
+                    fg_stroke: self.inactive_fg_stroke.to(),
+                    corner_radius: self.inactive_corner_radius.to(),
+                    expansion: self.inactive_expansion,
+                },
+                hovered: WidgetVisuals {
+                    bg_fill: self.hovered_bg_fill.to(),
+                    weak_bg_fill: self.hovered_weak_bg_fill.to(),
+                    bg_stroke: self.hovered_bg_stroke.to(),
+                    fg_stroke: self.hovered_fg_stroke.to(),
+                    corner_radius: self.hovered_corner_radius.to(),
+                    expansion: self.hovered_expansion,
+                },
+                active: WidgetVisuals {
+                    bg_fill: self.active_bg_fill.to(),
+                    weak_bg_fill: self.active_weak_bg_fill.to(),
+                    bg_stroke: self.active_bg_stroke.to(),
+                    fg_stroke: self.active_fg_stroke.to(),
+                    corner_radius: self.active_corner_radius.to(),
+                    expansion: self.active_expansion,
+                },
+                open: WidgetVisuals {
+                    bg_fill: self.open_bg_fill.to(),
+                    weak_bg_fill: self.open_weak_bg_fill.to(),
+                    bg_stroke: self.open_bg_stroke.to(),
+                    fg_stroke: self.open_fg_stroke.to(),
+                    corner_radius: self.open_corner_radius.to(),
+                    expansion: self.open_expansion,
+                },
+            },
+            selection: Selection {
+                bg_fill: self.selection_bg_fill.to(),
+                stroke: self.selection_stroke.to(),
+            },
+            window_shadow: Shadow {
+                color: self.window_shadow_color.to(),
+                offset: self.window_shadow_offset,
+                blur: self.window_shadow_blur,
+                spread: self.window_shadow_spread,
+            },
+            popup_shadow: Shadow {
+                color: self.popup_shadow_color.to(),
+                offset: self.popup_shadow_offset,
+                blur: self.popup_shadow_blur,
+                spread: self.popup_shadow_spread,
+            },
+            ..old_visual
+        };
+    }
+}
@@ -0,0 +1,271 @@
+#[cfg(feature = "gui")]
+use egui::{Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: WindowWidth,
+    pub anchor: VerticalAnchor,
+    pub offset: f32,
+    pub monitor: MonitorSelection,
+    pub max_results: Option<usize>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: WindowWidth::Fraction(0.4),
+            anchor: VerticalAnchor::Center,
+            offset: 0.,
+            monitor: MonitorSelection::Primary,
+            max_results: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[serde(try_from = "WidthSpec", into = "WidthSpec")]
+pub enum WindowWidth {
+    Points(f32),
+    Fraction(f32),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum WidthSpec {
+    Points(f32),
+    Percent(String),
+}
+
+impl TryFrom<WidthSpec> for WindowWidth {
+    type Error = String;
+
+    fn try_from(spec: WidthSpec) -> Result<Self, Self::Error> {
+        match spec {
+            WidthSpec::Points(points) if points > 0. => Ok(WindowWidth::Points(points)),
+            WidthSpec::Percent(percent) => percent
+                .trim()
+                .strip_suffix('%')
+                .and_then(|p| p.trim().parse::<f32>().ok())
+                .filter(|p| *p > 0. && *p <= 100.)
+                .map(|p| WindowWidth::Fraction(p / 100.))
+                .ok_or_else(|| format!("invalid window width `{percent}`, expected e.g. \"40%\"")),
+            WidthSpec::Points(points) => Err(format!("invalid window width `{points}`")),
+        }
+    }
+}
+
+impl From<WindowWidth> for WidthSpec {
+    fn from(width: WindowWidth) -> Self {
+        match width {
+            WindowWidth::Points(points) => WidthSpec::Points(points),
+            WindowWidth::Fraction(fraction) => WidthSpec::Percent(format!("{}%", fraction * 100.)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalAnchor {
+    Top,
+    #[default]
+    Center,
+    Bottom,
+}
+
+impl WindowConfig {
+    // `monitor` and the returned position are in points. `bar_height` is the
+    // height of the query bar, which is what gets centered for `Center`.
+    #[cfg(feature = "gui")]
+    pub fn placement(&self, monitor: Rect, height: f32, bar_height: f32) -> Rect {
+        let width = match self.width {
+            WindowWidth::Points(points) => points.min(monitor.width()),
+            WindowWidth::Fraction(fraction) => monitor.width() * fraction,
+        };
+
+        let y = match self.anchor {
+            VerticalAnchor::Top => monitor.top() + self.offset,
+            VerticalAnchor::Center => monitor.center().y - bar_height / 2. + self.offset,
+            VerticalAnchor::Bottom => monitor.bottom() - self.offset - height,
+        };
+
+        Rect::from_min_size(
+            Pos2::new(monitor.center().x - width / 2., y),
+            Vec2::new(width, height),
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorSelection {
+    #[default]
+    Primary,
+    Pointer,
+    Focused,
+}
+
+// Monitor geometry in physical pixels, in the desktop's global coordinate space.
+#[cfg(feature = "gui")]
+pub fn find(selection: MonitorSelection) -> Option<Rect> {
+    #[cfg(all(feature = "gui", unix, not(target_os = "macos")))]
+    {
+        x11::find(selection)
+            .inspect_err(|e| log::debug!("Failed to query X11 monitors: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    #[cfg(not(all(feature = "gui", unix, not(target_os = "macos"))))]
+    {
+        let _ = selection;
+        None
+    }
+}
+
+#[cfg(all(feature = "gui", unix, not(target_os = "macos")))]
+fn containing(monitors: &[(Rect, bool)], point: Option<Pos2>) -> Option<Rect> {
+    point
+        .and_then(|point| monitors.iter().find(|(rect, _)| rect.contains(point)))
+        .or_else(|| monitors.iter().find(|(_, primary)| *primary))
+        .or_else(|| monitors.first())
+        .map(|(rect, _)| *rect)
+}
+
+#[cfg(all(feature = "gui", unix, not(target_os = "macos")))]
+mod x11 {
+    use super::{MonitorSelection, containing};
+    use egui::{Pos2, Rect, Vec2};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::randr::ConnectionExt as _;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+
+    pub fn find(selection: MonitorSelection) -> anyhow::Result<Option<Rect>> {
+        if std::env::var_os("DISPLAY").is_none() {
+            return Ok(None);
+        }
+
+        let (conn, screen) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen].root;
+
+        let monitors: Vec<(Rect, bool)> = conn
+            .randr_get_monitors(root, true)?
+            .reply()?
+            .monitors
+            .into_iter()
+            .map(|m| {
+                let rect = Rect::from_min_size(
+                    Pos2::new(m.x as f32, m.y as f32),
+                    Vec2::new(m.width as f32, m.height as f32),
+                );
+                (rect, m.primary)
+            })
+            .collect();
+
+        let point = match selection {
+            MonitorSelection::Primary => None,
+            MonitorSelection::Pointer => {
+                let pointer = conn.query_pointer(root)?.reply()?;
+                Some(Pos2::new(pointer.root_x as f32, pointer.root_y as f32))
+            }
+            MonitorSelection::Focused => {
+                let atom = conn
+                    .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
+                    .reply()?
+                    .atom;
+                let active = conn
+                    .get_property(false, root, atom, AtomEnum::WINDOW, 0, 1)?
+                    .reply()?
+                    .value32()
+                    .and_then(|mut v| v.next())
+                    .filter(|&window| window != 0);
+
+                match active {
+                    Some(window) => {
+                        let origin = conn.translate_coordinates(window, root, 0, 0)?.reply()?;
+                        let geometry = conn.get_geometry(window)?.reply()?;
+                        Some(Pos2::new(
+                            origin.dst_x as f32 + geometry.width as f32 / 2.,
+                            origin.dst_y as f32 + geometry.height as f32 / 2.,
+                        ))
+                    }
+                    None => None,
+                }
+            }
+        };
+
+        Ok(containing(&monitors, point))
+    }
+}
+
+// Used when the monitor layout cannot be queried (e.g. on Wayland, where the
+// compositor decides placement); only the size of the current monitor is known.
+#[cfg(feature = "gui")]
+pub fn fallback(monitor_size: Option<Vec2>) -> Option<Rect> {
+    monitor_size.map(|size| Rect::from_min_size(Pos2::ZERO, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width(toml: &str) -> Result<WindowWidth, toml::de::Error> {
+        toml::from_str::<WindowConfig>(&format!("width = {toml}")).map(|config| config.width)
+    }
+
+    #[test]
+    fn parses_widths() {
+        assert_eq!(width("\"40%\"").unwrap(), WindowWidth::Fraction(0.4));
+        assert_eq!(width("\" 100 % \"").unwrap(), WindowWidth::Fraction(1.));
+        assert_eq!(width("600").unwrap(), WindowWidth::Points(600.));
+        assert_eq!(width("720.5").unwrap(), WindowWidth::Points(720.5));
+        for invalid in ["0", "-10", "\"0%\"", "\"150%\"", "\"40\"", "\"wide\""] {
+            assert!(width(invalid).is_err(), "{invalid}");
+        }
+
+        let config = WindowConfig {
+            width: WindowWidth::Fraction(0.25),
+            ..Default::default()
+        };
+        let toml = toml::to_string(&config).unwrap();
+        assert!(toml.contains("width = \"25%\""), "{toml}");
+        assert_eq!(toml::from_str::<WindowConfig>(&toml).unwrap(), config);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn places_the_window_on_the_monitor() {
+        // A 1000x800 monitor right of another.
+        let monitor = Rect::from_min_size(Pos2::new(1000., 100.), Vec2::new(1000., 800.));
+        let place = |width, anchor| {
+            let config = WindowConfig {
+                width,
+                anchor,
+                offset: 10.,
+                ..Default::default()
+            };
+            config.placement(monitor, 300., 50.)
+        };
+
+        let fraction = WindowWidth::Fraction(0.4);
+        for (anchor, top) in [
+            (VerticalAnchor::Top, 110.),
+            // The query bar is centered, and the results hang below it.
+            (VerticalAnchor::Center, 485.),
+            (VerticalAnchor::Bottom, 590.),
+        ] {
+            assert_eq!(
+                place(fraction, anchor),
+                Rect::from_min_size(Pos2::new(1300., top), Vec2::new(400., 300.)),
+                "{anchor:?}"
+            );
+        }
+
+        let rect = place(WindowWidth::Points(600.), VerticalAnchor::Top);
+        assert_eq!((rect.left(), rect.width()), (1200., 600.));
+        // Never wider than the monitor.
+        let rect = place(WindowWidth::Points(1500.), VerticalAnchor::Top);
+        assert_eq!((rect.left(), rect.width()), (1000., 1000.));
+    }
+}